@@ -0,0 +1,799 @@
+use crate::{
+    commands::{parse_day, parse_duration, parse_query, parse_task, TaskOrRelative},
+    config::Config,
+    db::Db,
+    dump::{Dump, ImportMode},
+    error::{Error, Result},
+    ical, markdown,
+    recurrence::Recurrence,
+    taskwarrior,
+    timesheet::{self, Group},
+    todotxt,
+    types::{
+        local_date, start_of_day, Day, RelationshipSide, StatusChange, Task, TaskId, TaskStatus,
+        DATE_FORMAT,
+    },
+    views::sessions,
+};
+use chrono::{DateTime, Duration, Local, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/// Exit codes, besides 0 for success and 2 for invalid usage, which clap exits with.
+const EXIT_ERROR: i32 = 1;
+const EXIT_BLOCKED: i32 = 3;
+const EXIT_NOT_FOUND: i32 = 4;
+
+/// A stack based task manager. Without a command, tsk opens the interactive view.
+#[derive(Parser, Debug)]
+#[command(
+    name = "tsk",
+    version,
+    after_help = "Exit codes: 0 success, 1 error, 2 invalid usage, 3 task is blocked, 4 no such task"
+)]
+pub(super) struct Cli {
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Tasks are given as `tsk-4`, or as a position on the stack where `0` is the top.
+#[derive(Subcommand, Debug)]
+pub(super) enum CliCommand {
+    /// Push a new task onto the top of the stack
+    Push {
+        #[arg(required = true)]
+        title: Vec<String>,
+    },
+    /// List the tasks at the top of the stack
+    List {
+        /// How many tasks to list, defaults to num_top_tasks from the config
+        #[arg(short = 'n', long)]
+        count: Option<u16>,
+    },
+    /// Show everything known about a task
+    Show {
+        #[arg(value_parser = task_arg)]
+        task: TaskOrRelative,
+    },
+    /// Complete a task, the top one by default
+    Complete {
+        #[arg(value_parser = task_arg)]
+        task: Option<TaskOrRelative>,
+    },
+    /// Start a task, the top one by default
+    Start {
+        #[arg(value_parser = task_arg)]
+        task: Option<TaskOrRelative>,
+    },
+    /// Take a task off the stack without completing it, the top one by default
+    Drop {
+        #[arg(value_parser = task_arg)]
+        task: Option<TaskOrRelative>,
+    },
+    /// Swap the top two tasks
+    Swap,
+    /// Set the day a task is due: `today`, `tomorrow`, a date like 2026-11-01, or `none`
+    Due {
+        #[arg(value_parser = task_arg)]
+        task: TaskOrRelative,
+        #[arg(value_parser = day_arg)]
+        date: DayOrNone,
+    },
+    /// Set the day work on a task is planned to start, like `due`
+    Schedule {
+        #[arg(value_parser = task_arg)]
+        task: TaskOrRelative,
+        #[arg(value_parser = day_arg)]
+        date: DayOrNone,
+    },
+    /// Repeat a task `daily`, `weekly`, `weekly mon,thu`, `monthly` or `every N days`, or `none`
+    /// to stop. Completing it creates the next instance
+    Repeat {
+        #[arg(value_parser = task_arg)]
+        task: TaskOrRelative,
+        #[arg(required = true)]
+        rule: Vec<String>,
+    },
+    /// Stop the clock on a task, by default the first one in progress on the stack
+    Pause {
+        #[arg(value_parser = task_arg)]
+        task: Option<TaskOrRelative>,
+    },
+    /// Start the clock on a task again, by default the one paused last
+    Resume {
+        #[arg(value_parser = task_arg)]
+        task: Option<TaskOrRelative>,
+    },
+    /// Add time spent on a task that wasn't tracked, e.g. `1h30m`, or take it off with `-15m`
+    Log {
+        #[arg(value_parser = task_arg)]
+        task: TaskOrRelative,
+        #[arg(value_parser = duration_arg, allow_hyphen_values = true)]
+        duration: Duration,
+        /// The day the time was spent, today by default
+        #[arg(value_parser = date_arg)]
+        day: Option<Day>,
+    },
+    /// Add up the time spent on tasks, to FILE or standard output
+    Report {
+        file: Option<PathBuf>,
+        /// What to group the time by
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "day,task")]
+        by: Vec<Group>,
+        /// The first day to include
+        #[arg(long, value_parser = date_arg)]
+        from: Option<Day>,
+        /// The last day to include
+        #[arg(long, value_parser = date_arg)]
+        to: Option<Day>,
+        /// The format to write, `--json` writes JSON too
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// List the tasks matching a query, e.g. `tsk query +work status:todo`
+    Query {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Write the whole database to FILE or standard output. Only the tsk format keeps everything
+    Export {
+        file: Option<PathBuf>,
+        /// The format to write
+        #[arg(long, value_enum, default_value_t = ExportFormat::Tsk)]
+        format: ExportFormat,
+        /// Add each task's status history, for Markdown
+        #[arg(long)]
+        history: bool,
+    },
+    /// Read a file written by `tsk export` or another task manager. Only an empty database is
+    /// restored without `--merge` or `--replace`
+    Import {
+        file: PathBuf,
+        /// The format FILE is in
+        #[arg(long, value_enum, default_value_t = ImportFormat::Tsk)]
+        format: ImportFormat,
+        /// Add the tasks under new IDs, below the existing stack
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+        /// Back up and delete everything first, keeping the original IDs
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Undo the last change
+    Undo,
+    /// Redo the last undone change
+    Redo,
+}
+
+/// Formats `tsk import` reads.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ImportFormat {
+    /// Written by `tsk export`
+    Tsk,
+    /// Written by `task export`
+    Taskwarrior,
+    /// A todo.txt file
+    #[value(name = "todotxt")]
+    TodoTxt,
+    /// An iCalendar file, skipping tasks imported from it before when merging
+    Ical,
+}
+
+/// Formats `tsk export` writes.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ExportFormat {
+    /// JSON that `tsk import` restores losslessly
+    Tsk,
+    /// A todo.txt file, in stack order
+    #[value(name = "todotxt")]
+    TodoTxt,
+    /// A Markdown checklist of the top of the stack
+    Markdown,
+    /// An iCalendar file with a VTODO for every task
+    Ical,
+}
+
+/// Formats `tsk report` writes.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ReportFormat {
+    /// A table with hours and minutes
+    Text,
+    /// A row per group with seconds and decimal hours
+    Csv,
+    Json,
+}
+
+fn task_arg(input: &str) -> std::result::Result<TaskOrRelative, String> {
+    parse_task(input).ok_or_else(|| format!("expected tsk-N or a stack position, got `{input}`"))
+}
+
+/// An alias so clap doesn't take `Option` to mean the argument can be left out; `none` clears a
+/// date.
+type DayOrNone = Option<Day>;
+
+fn day_arg(input: &str) -> std::result::Result<DayOrNone, String> {
+    parse_day(input).ok_or_else(|| {
+        format!("expected today, tomorrow, a date like 2026-11-01 or none, got `{input}`")
+    })
+}
+
+fn date_arg(input: &str) -> std::result::Result<Day, String> {
+    parse_day(input).flatten().ok_or_else(|| {
+        format!("expected today, yesterday, tomorrow or a date like 2026-11-01, got `{input}`")
+    })
+}
+
+fn duration_arg(input: &str) -> std::result::Result<Duration, String> {
+    parse_duration(input).ok_or_else(|| format!("expected a duration like 1h30m, got `{input}`"))
+}
+
+/// `exit_code` is the process exit code for a command that failed with `error`.
+pub(super) fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Blocked(_) => EXIT_BLOCKED,
+        Error::NotFound(_) => EXIT_NOT_FOUND,
+        _ => EXIT_ERROR,
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339()
+}
+
+fn task_json(task: &Task) -> Value {
+    let content = task.content.as_ref();
+    json!({
+        "id": task.id,
+        "title": task.title,
+        "status": task.status.name(),
+        "created": timestamp(task.created),
+        "body": content.and_then(|c| c.body.as_ref()),
+        "link": content.and_then(|c| c.link.as_ref()).map(|l| l.to_string()),
+        "tags": task.tags.iter().map(|t| t.as_str()).collect::<Vec<_>>(),
+        "metadata": task.metadata,
+        "due": task.due.map(|d| local_date(d).format(DATE_FORMAT).to_string()),
+        "scheduled": task.scheduled.map(|d| local_date(d).format(DATE_FORMAT).to_string()),
+        "recurrence": task.recurrence.as_ref().map(|r| r.to_string()),
+        "relationships": task.relationships.iter().map(|r| {
+            let (direction, other) = match r.side {
+                RelationshipSide::Left(other) => ("from", other),
+                RelationshipSide::Right(other) => ("to", other),
+            };
+            json!({ "tag": r.tag, direction: other, "title": r.title })
+        }).collect::<Vec<_>>(),
+    })
+}
+
+fn history_json(history: &[StatusChange]) -> Value {
+    history
+        .iter()
+        .map(|change| {
+            json!({
+                "status": change.status.name(),
+                "updated": timestamp(change.updated),
+                "until": change.until.map(timestamp),
+            })
+        })
+        .collect()
+}
+
+fn task_line(task: &Task) -> String {
+    format!("{} TSK-{} {}", task.status, task.id, task.title)
+}
+
+/// `resolve` finds the task `task` refers to, the top of the stack when it's `None`.
+fn resolve(db: &Db, task: Option<TaskOrRelative>) -> Result<TaskId> {
+    let task_id = match task {
+        Some(TaskOrRelative::Task(task_id)) => task_id,
+        Some(TaskOrRelative::Relative(i)) => db
+            .get_top_n_tasks(i as u16 + 1)?
+            .get(i as usize)
+            .map(|t| t.id)
+            .ok_or_else(|| Error::NotFound(format!("There is no task at position {i}")))?,
+        None => db
+            .get_top_n_tasks(1)?
+            .first()
+            .map(|t| t.id)
+            .ok_or_else(|| Error::NotFound("The stack is empty".to_string()))?,
+    };
+    db.get_task(task_id)
+        .map_err(|_| Error::NotFound(format!("TSK-{task_id} doesn't exist")))?;
+    Ok(task_id)
+}
+
+/// Prints `text` normally, or `json` in `--json` mode.
+fn output(json: bool, text: String, value: Value) {
+    if json {
+        println!("{value}");
+    } else if !text.is_empty() {
+        println!("{text}");
+    }
+}
+
+/// `run` runs a single command against the database and prints its result.
+pub(super) fn run(command: CliCommand, json: bool, db: &mut Db, config: &Config) -> Result<()> {
+    match command {
+        CliCommand::Push { title } => {
+            let title = title.join(" ");
+            let task_id = db.push_task(title)?;
+            let task = db.get_task(task_id)?;
+            output(json, format!("TSK-{task_id}"), task_json(&task));
+        }
+        CliCommand::List { count } => {
+            let tasks = db.get_top_n_tasks(count.unwrap_or(config.num_top_tasks))?;
+            let mut lines = Vec::with_capacity(tasks.len());
+            let mut values = Vec::with_capacity(tasks.len());
+            for (i, task) in tasks.iter().enumerate() {
+                lines.push(format!("{i}: {}", task_line(task)));
+                values.push(task_json(&db.get_task(task.id)?));
+            }
+            output(json, lines.join("\n"), Value::Array(values));
+        }
+        CliCommand::Show { task } => {
+            let task = db.get_task(resolve(db, Some(task))?)?;
+            let history = db.get_status_history(task.id)?;
+            let mut value = task_json(&task);
+            value["history"] = history_json(&history);
+            let date = |time: DateTime<Utc>| {
+                time.with_timezone(&Local)
+                    .format(&config.date_format)
+                    .to_string()
+            };
+            let mut lines = vec![
+                format!("TSK-{} {}", task.id, task.title),
+                format!("Status:  {} {:?}", task.status, task.status),
+                format!("Created: {}", date(task.created)),
+            ];
+            if let Some(due) = task.due {
+                lines.push(format!("Due:     {}", local_date(due).format(DATE_FORMAT)));
+            }
+            if let Some(scheduled) = task.scheduled {
+                lines.push(format!(
+                    "Scheduled: {}",
+                    local_date(scheduled).format(DATE_FORMAT)
+                ));
+            }
+            if let Some(rule) = &task.recurrence {
+                lines.push(format!("Repeats: {rule}"));
+            }
+            let spent = db.time_spent(task.id, Utc::now())?;
+            value["seconds_spent"] = json!(spent.num_seconds());
+            if spent != Duration::zero() {
+                lines.push(format!("Time:    {}", timesheet::hours(spent)));
+            }
+            let focus_sessions = db.get_focus_sessions(task.id)?;
+            value["focus_sessions"] = json!(focus_sessions.len());
+            if !focus_sessions.is_empty() {
+                lines.push(format!("Focus:   {}", sessions(focus_sessions.len())));
+            }
+            if !task.tags.is_empty() {
+                let tags = task.tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                lines.push(format!("Tags:    {}", tags.join(" ")));
+            }
+            if let Some(link) = task.content.as_ref().and_then(|c| c.link.as_ref()) {
+                lines.push(format!("Link:    {link}"));
+            }
+            for (key, value) in &task.metadata {
+                lines.push(format!("{key}: {value}"));
+            }
+            for r in &task.relationships {
+                lines.push(match r.side {
+                    RelationshipSide::Left(other) => format!("{} ← TSK-{other} {}", r.tag, r.title),
+                    RelationshipSide::Right(other) => {
+                        format!("{} → TSK-{other} {}", r.tag, r.title)
+                    }
+                });
+            }
+            if let Some(body) = task.content.as_ref().and_then(|c| c.body.as_ref()) {
+                lines.push(String::new());
+                lines.push(body.clone());
+            }
+            lines.push(String::new());
+            for change in &history {
+                lines.push(format!("{}  {:?}", date(change.updated), change.status));
+            }
+            output(json, lines.join("\n"), value);
+        }
+        CliCommand::Complete { task } => {
+            let task_id = resolve(db, task)?;
+            let next = db.complete(task_id, config.recurring_position)?;
+            let mut value = task_json(&db.get_task(task_id)?);
+            let mut text = format!("Completed TSK-{task_id}");
+            if let Some(next) = next {
+                let next = db.get_task(next)?;
+                text.push_str(&format!("\nRepeats as TSK-{}", next.id));
+                if let Some(due) = next.due {
+                    text.push_str(&format!(", due {}", local_date(due).format(DATE_FORMAT)));
+                }
+                value["next"] = task_json(&next);
+            }
+            output(json, text, value);
+        }
+        CliCommand::Start { task } => {
+            let task_id = resolve(db, task)?;
+            db.update_status(task_id, TaskStatus::InProgress)?;
+            output(
+                json,
+                format!("Started TSK-{task_id}"),
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Drop { task } => {
+            let task_id = resolve(db, task)?;
+            db.deprioritize(task_id)?;
+            output(
+                json,
+                format!("Dropped TSK-{task_id}"),
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Swap => {
+            let tasks = db.get_top_n_tasks(2)?;
+            let second = tasks.get(1).ok_or_else(|| {
+                Error::NotFound("Swapping needs at least two tasks on the stack".to_string())
+            })?;
+            db.prioritize(second.id)?;
+            output(
+                json,
+                format!("Prioritized TSK-{}", second.id),
+                task_json(&db.get_task(second.id)?),
+            );
+        }
+        CliCommand::Due { task, date } => {
+            let task_id = resolve(db, Some(task))?;
+            let date = date.map(|d| d.date());
+            db.set_due(task_id, date.map(start_of_day))?;
+            output(
+                json,
+                match date {
+                    Some(date) => format!("TSK-{task_id} is due {}", date.format(DATE_FORMAT)),
+                    None => format!("TSK-{task_id} is no longer due"),
+                },
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Schedule { task, date } => {
+            let task_id = resolve(db, Some(task))?;
+            let date = date.map(|d| d.date());
+            db.set_scheduled(task_id, date.map(start_of_day))?;
+            output(
+                json,
+                match date {
+                    Some(date) => {
+                        format!(
+                            "TSK-{task_id} is scheduled for {}",
+                            date.format(DATE_FORMAT)
+                        )
+                    }
+                    None => format!("TSK-{task_id} is no longer scheduled"),
+                },
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Repeat { task, rule } => {
+            let task_id = resolve(db, Some(task))?;
+            let rule = match rule.join(" ").as_str() {
+                "none" => None,
+                rule => Some(rule.parse::<Recurrence>()?),
+            };
+            db.set_recurrence(task_id, rule.as_ref())?;
+            output(
+                json,
+                match rule {
+                    Some(rule) => format!("TSK-{task_id} repeats {rule}"),
+                    None => format!("TSK-{task_id} no longer repeats"),
+                },
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Pause { task } => {
+            let task_id = match task {
+                Some(task) => resolve(db, Some(task))?,
+                None => db
+                    .get_top_n_tasks(config.num_top_tasks)?
+                    .iter()
+                    .find(|t| t.status == TaskStatus::InProgress)
+                    .map(|t| t.id)
+                    .ok_or_else(|| Error::NotFound("No task in progress".to_string()))?,
+            };
+            db.pause(task_id)?;
+            let spent = db.time_spent(task_id, Utc::now())?;
+            let mut value = task_json(&db.get_task(task_id)?);
+            value["seconds_spent"] = json!(spent.num_seconds());
+            output(
+                json,
+                format!("Paused TSK-{task_id} at {}", timesheet::hours(spent)),
+                value,
+            );
+        }
+        CliCommand::Resume { task } => {
+            let task_id = match task {
+                Some(task) => resolve(db, Some(task))?,
+                None => match db.last_paused()? {
+                    Some(task_id) => task_id,
+                    None => resolve(db, None)?,
+                },
+            };
+            db.resume(task_id)?;
+            output(
+                json,
+                format!("Resumed TSK-{task_id}"),
+                task_json(&db.get_task(task_id)?),
+            );
+        }
+        CliCommand::Log {
+            task,
+            duration,
+            day,
+        } => {
+            let task_id = resolve(db, Some(task))?;
+            let day = day.unwrap_or(Day::Today);
+            db.log_time(task_id, start_of_day(day.date()), duration)?;
+            let spent = db.time_spent(task_id, Utc::now())?;
+            let mut value = task_json(&db.get_task(task_id)?);
+            value["seconds_spent"] = json!(spent.num_seconds());
+            output(
+                json,
+                format!(
+                    "Logged {} on TSK-{task_id} {day}, {} in total",
+                    timesheet::hours(duration),
+                    timesheet::hours(spent)
+                ),
+                value,
+            );
+        }
+        CliCommand::Report {
+            file,
+            by,
+            from,
+            to,
+            format,
+        } => {
+            let report = timesheet::report(
+                db,
+                &by,
+                from.map(|d| d.date()),
+                to.map(|d| d.date()),
+                Utc::now(),
+            )?;
+            let format = if json { ReportFormat::Json } else { format };
+            let text = match format {
+                ReportFormat::Json => report.to_json().to_string() + "\n",
+                ReportFormat::Text => report.to_text(),
+                ReportFormat::Csv => report.to_csv(),
+            };
+            match file {
+                Some(file) => fs::write(file, text)?,
+                None => print!("{text}"),
+            }
+        }
+        CliCommand::Query { query } => {
+            let query = parse_query(&query.join(" ")).map_err(|e| Error::Parse(e.to_string()))?;
+            let results = db.query(&query)?;
+            let mut lines = Vec::with_capacity(results.len());
+            let mut values = Vec::with_capacity(results.len());
+            for result in results {
+                let task = db.get_task(result.task.id)?;
+                lines.push(task_line(&task));
+                values.push(task_json(&task));
+            }
+            output(json, lines.join("\n"), Value::Array(values));
+        }
+        CliCommand::Export {
+            file,
+            format,
+            history,
+        } => {
+            let text = match format {
+                ExportFormat::Tsk => db.export()?.to_json()? + "\n",
+                ExportFormat::TodoTxt => todotxt::from_dump(&db.export()?),
+                ExportFormat::Markdown => markdown::render(db, config, history)?,
+                ExportFormat::Ical => ical::from_dump(&db.export()?),
+            };
+            match file {
+                Some(file) => fs::write(file, text)?,
+                None => print!("{text}"),
+            }
+        }
+        CliCommand::Import {
+            file,
+            format,
+            merge,
+            replace,
+        } => {
+            let contents = fs::read_to_string(file)?;
+            let mode = if merge {
+                ImportMode::Merge
+            } else if replace || db.is_empty()? {
+                ImportMode::Replace
+            } else {
+                return Err(Error::Database(
+                    "The database isn't empty, import with --merge or --replace".to_string(),
+                ));
+            };
+            let dump = match format {
+                ImportFormat::Tsk => Dump::from_json(&contents)?,
+                ImportFormat::Taskwarrior => taskwarrior::to_dump(&contents)?,
+                ImportFormat::TodoTxt => todotxt::to_dump(&contents)?,
+                // replacing deletes the existing tasks, so none of the file's can be skipped
+                ImportFormat::Ical if mode == ImportMode::Replace => {
+                    ical::to_dump(&contents, &Dump::new())?
+                }
+                ImportFormat::Ical => ical::to_dump(&contents, &db.export()?)?,
+            };
+            let ids = db.import(&dump, mode)?;
+            output(
+                json,
+                format!("Imported {} tasks", ids.len()),
+                json!({
+                    "imported": ids.len(),
+                    "ids": ids
+                        .iter()
+                        .map(|(old, new)| (old.to_string(), json!(new)))
+                        .collect::<serde_json::Map<_, _>>(),
+                }),
+            );
+        }
+        CliCommand::Undo => {
+            let undone = db.undo()?;
+            let text = match &undone {
+                Some(name) => format!("Undid {name}"),
+                None => "Nothing to undo".to_string(),
+            };
+            output(json, text, json!({ "undone": undone }));
+        }
+        CliCommand::Redo => {
+            let redone = db.redo()?;
+            let text = match &redone {
+                Some(name) => format!("Redid {name}"),
+                None => "Nothing to redo".to_string(),
+            };
+            output(json, text, json!({ "redone": redone }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn run_command(db: &mut Db, args: &[&str]) -> Result<()> {
+        let cli = Cli::try_parse_from(std::iter::once("tsk").chain(args.iter().copied()))
+            .expect("valid arguments");
+        run(cli.command.unwrap(), cli.json, db, &Config::default())
+    }
+
+    #[test]
+    fn test_parse_cli() {
+        let cli = Cli::try_parse_from(["tsk", "complete", "TSK-5", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Complete {
+                task: Some(TaskOrRelative::Task(5))
+            })
+        ));
+        let cli = Cli::try_parse_from(["tsk"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(Cli::try_parse_from(["tsk", "show", "five"]).is_err());
+        assert!(Cli::try_parse_from(["tsk", "show", "tsk-99999999999999999999"]).is_err());
+        assert!(Cli::try_parse_from(["tsk", "push"]).is_err());
+        let cli = Cli::try_parse_from(["tsk", "log", "tsk-2", "-15m", "yesterday"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Log {
+                task: TaskOrRelative::Task(2),
+                day: Some(Day::Yesterday),
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["tsk", "log", "tsk-2", "90"]).is_err());
+        assert!(Cli::try_parse_from(["tsk", "log", "tsk-2", "1h", "none"]).is_err());
+        let cli = Cli::try_parse_from(["tsk", "report", "--by", "tag,day", "--format", "csv"]);
+        assert!(matches!(
+            cli.unwrap().command,
+            Some(CliCommand::Report { by, format: ReportFormat::Csv, .. })
+                if by == [Group::Tag, Group::Day]
+        ));
+        let cli = Cli::try_parse_from(["tsk", "import", "--format", "taskwarrior", "t.json"]);
+        assert!(matches!(
+            cli.unwrap().command,
+            Some(CliCommand::Import {
+                format: ImportFormat::Taskwarrior,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_run_commands() -> Result<()> {
+        let mut db = Db::in_memory()?;
+        run_command(&mut db, &["push", "write", "report"])?;
+        run_command(&mut db, &["push", "review"])?;
+        let top = db.get_top_n_tasks(2)?;
+        assert_eq!(top[0].title, "review");
+        assert_eq!(top[1].title, "write report");
+
+        run_command(&mut db, &["swap"])?;
+        assert_eq!(db.get_top_n_tasks(1)?[0].id, 1);
+        run_command(&mut db, &["start", "--json"])?;
+        assert_eq!(db.get_task(1)?.status, TaskStatus::InProgress);
+        run_command(&mut db, &["complete", "1"])?;
+        assert_eq!(db.get_task(2)?.status, TaskStatus::Complete);
+        run_command(&mut db, &["show", "tsk-2"])?;
+
+        let error = run_command(&mut db, &["complete", "tsk-9"]).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_NOT_FOUND);
+        let error = run_command(&mut db, &["complete", "5"]).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_NOT_FOUND);
+
+        run_command(&mut db, &["push", "blocked"])?;
+        db.connect(1, "blocks", 3)?;
+        let error = run_command(&mut db, &["complete"]).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_BLOCKED);
+
+        run_command(&mut db, &["due", "tsk-3", "2026-11-01"])?;
+        run_command(&mut db, &["schedule", "0", "today"])?;
+        let task = db.get_task(3)?;
+        assert_eq!(
+            task.due.map(local_date),
+            NaiveDate::from_ymd_opt(2026, 11, 1)
+        );
+        assert_eq!(task.scheduled.map(local_date), Some(Day::Today.date()));
+        run_command(&mut db, &["due", "tsk-3", "none"])?;
+        assert_eq!(db.get_task(3)?.due, None);
+        assert!(Cli::try_parse_from(["tsk", "due", "tsk-3", "soon"]).is_err());
+        assert!(Cli::try_parse_from(["tsk", "due", "tsk-3"]).is_err());
+
+        run_command(&mut db, &["pause"])?;
+        assert_eq!(db.get_task(1)?.status, TaskStatus::Todo);
+        let error = run_command(&mut db, &["pause"]).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_NOT_FOUND);
+        run_command(&mut db, &["resume", "tsk-1"])?;
+        assert_eq!(db.get_task(1)?.status, TaskStatus::InProgress);
+        run_command(&mut db, &["log", "tsk-3", "1h30m", "yesterday"])?;
+        run_command(&mut db, &["log", "tsk-3", "-30m", "yesterday"])?;
+        assert_eq!(db.time_spent(3, Utc::now())?, Duration::hours(1));
+        run_command(&mut db, &["report", "--by", "tag,task", "--json"])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_ical() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("tsk-import-{}.ics", std::process::id()));
+        fs::write(
+            &path,
+            "BEGIN:VTODO\r\nUID:a@example.com\r\nSUMMARY:Renew passport\r\nEND:VTODO\r\n",
+        )?;
+        let file = path.to_str().unwrap();
+        let mut db = Db::in_memory()?;
+        run_command(&mut db, &["import", "--format", "ical", file])?;
+        run_command(&mut db, &["import", "--format", "ical", "--merge", file])?;
+        assert_eq!(db.export()?.tasks.len(), 1);
+        run_command(&mut db, &["import", "--format", "ical", "--replace", file])?;
+        let tasks = db.export()?.tasks;
+        fs::remove_file(&path)?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Renew passport");
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_json() -> Result<()> {
+        let mut db = Db::in_memory()?;
+        let task_id = db.push_task("json".to_string())?;
+        let value = task_json(&db.get_task(task_id)?);
+        assert_eq!(value["id"], 1);
+        assert_eq!(value["title"], "json");
+        assert_eq!(value["status"], "todo");
+        assert_eq!(value["body"], Value::Null);
+        Ok(())
+    }
+}
@@ -1,10 +1,18 @@
 #![allow(dead_code)]
-use crate::types::TaskId;
+use std::fmt::Display;
 
-use combine::error::ParseError;
-use combine::parser::char::{alpha_num, char, digit, spaces, string};
+use crate::recurrence::Recurrence;
+use crate::types::{
+    BacklogOrder, Day, DueFilter, QueryArgs, RelationshipSide, TaskId, TaskStatus, DATE_FORMAT,
+    MAX_TIME_ENTRY_HOURS,
+};
+use chrono::{Duration, NaiveDate};
+
+use combine::error::{ParseError, StreamError};
+use combine::parser::char::{alpha_num, char, digit, letter, space, spaces, string};
 use combine::parser::repeat::repeat_until;
-use combine::{any, eof, many, many1, satisfy};
+use combine::stream::StreamErrorFor;
+use combine::{any, eof, look_ahead, many, many1, optional, satisfy};
 use combine::{
     attempt, between, parser::choice::choice, stream::position, EasyParser, Parser, Stream,
 };
@@ -79,10 +87,22 @@ simple_command! {
 simple_command!(Start);
 
 simple_command!(Quit);
+simple_command! {
+    Connect,
+    relationship -> (TaskOrRelative, String, TaskOrRelative)
+}
+simple_command! {
+    Disconnect,
+    relationship -> (TaskOrRelative, String, TaskOrRelative)
+}
+simple_command!(Backlog);
+simple_command!(Undo);
+simple_command!(Redo);
 simple_command!(Swap);
 simple_command!(Todo);
 simple_command!(Rot);
 simple_command!(NRot);
+simple_command!(Continue);
 simple_command! {
     Reprioritize,
     task_id -> TaskId
@@ -91,6 +111,50 @@ simple_command! {
     Make,
     name -> String
 }
+simple_command! {
+    Query,
+    query -> Vec<QueryArgs>
+}
+simple_command! {
+    Open,
+    task -> TaskOrRelative
+}
+simple_command! {
+    Markdown,
+    file -> (bool, String)
+}
+simple_command! {
+    Due,
+    due -> (Option<TaskOrRelative>, Option<Day>)
+}
+simple_command! {
+    Schedule,
+    scheduled -> (Option<TaskOrRelative>, Option<Day>)
+}
+simple_command! {
+    Repeat,
+    rule -> (Option<TaskOrRelative>, Option<Recurrence>)
+}
+simple_command! {
+    Pause,
+    task -> TaskOrRelative
+}
+simple_command! {
+    Resume,
+    task -> TaskOrRelative
+}
+simple_command! {
+    Log,
+    time -> (Option<TaskOrRelative>, Duration, Option<Day>)
+}
+simple_command! {
+    Focus,
+    minutes -> u16
+}
+simple_command! {
+    Break,
+    minutes -> u16
+}
 
 macro_rules! simple_parser(
     ($name:ident, $c:literal, $full:literal, $type:ty) => {
@@ -141,7 +205,11 @@ where
 {
     attempt(string("tsk-"))
         .with(many1(digit()))
-        .map(|s: String| s.parse::<TaskId>().unwrap())
+        .and_then(|s: String| {
+            s.parse::<TaskId>().map_err(|_| {
+                StreamErrorFor::<Input>::message_format(format!("invalid task `tsk-{s}`"))
+            })
+        })
 }
 
 fn make<Input>() -> impl Parser<Input, Output = Make>
@@ -156,15 +224,300 @@ where
         .map(|s: String| Make { name: Some(s) })
 }
 
+fn query_command<Input>() -> impl Parser<Input, Output = Query>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("query"))
+        .or(char('/').map(|_| "query"))
+        .skip(spaces())
+        .with(query())
+        .map(|q| Query { query: Some(q) })
+}
+
+fn open<Input>() -> impl Parser<Input, Output = Open>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("open"))
+        .or(char('o').map(|_| "open"))
+        .skip(spaces())
+        .with(optional(task_or_relative()))
+        .skip(spaces())
+        .skip(eof())
+        .map(|task| Open { task })
+}
+
+/// `markdown notes.md` writes the stack to notes.md, `markdown history notes.md` adds every
+/// task's status history.
+fn markdown<Input>() -> impl Parser<Input, Output = Markdown>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("markdown"))
+        .skip(spaces())
+        .with(optional(attempt(string("history").skip(space()))).map(|h| h.is_some()))
+        .skip(spaces())
+        .and(many1(any()))
+        .map(|(history, file): (bool, String)| Markdown {
+            file: Some((history, file.trim_end().to_string())),
+        })
+}
+
+/// `yesterday`, `today`, `tomorrow` or a date like `2026-11-01`
+fn day<Input>() -> impl Parser<Input, Output = Day>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        attempt(string("yesterday")).map(|_| Day::Yesterday),
+        attempt(string("today")).map(|_| Day::Today),
+        attempt(string("tomorrow")).map(|_| Day::Tomorrow),
+        many1(digit().or(char('-'))).and_then(|s: String| {
+            NaiveDate::parse_from_str(&s, DATE_FORMAT)
+                .map(Day::On)
+                .map_err(|_| StreamErrorFor::<Input>::message_format(format!("invalid date `{s}`")))
+        }),
+    ))
+}
+
+/// The arguments of `due` and `schedule`: an optional task, then a day or `none` to clear it.
+fn task_day<Input>() -> impl Parser<Input, Output = (Option<TaskOrRelative>, Option<Day>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    optional(attempt(task_or_relative().skip(space()).skip(spaces())))
+        .and(attempt(string("none")).map(|_| None).or(day().map(Some)))
+        .skip(spaces())
+        .skip(eof())
+}
+
+/// `due tsk-4 2026-11-01`, `due tomorrow` for the top task, `due 2 none` to clear it
+fn due<Input>() -> impl Parser<Input, Output = Due>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("due").skip(space()))
+        .skip(spaces())
+        .with(task_day())
+        .map(|d| Due { due: Some(d) })
+}
+
+/// `schedule tsk-4 2026-11-01`, like `due`
+fn schedule<Input>() -> impl Parser<Input, Output = Schedule>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("schedule").skip(space()))
+        .skip(spaces())
+        .with(task_day())
+        .map(|s| Schedule { scheduled: Some(s) })
+}
+
+/// `repeat tsk-4 weekly mon,thu`, `repeat every 10 days` for the top task, `repeat 2 none` to
+/// stop repeating
+fn repeat<Input>() -> impl Parser<Input, Output = Repeat>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let rule = many1(any()).and_then(|s: String| match s.trim() {
+        "none" => Ok(None),
+        rule => rule
+            .parse::<Recurrence>()
+            .map(Some)
+            .map_err(StreamErrorFor::<Input>::message_format),
+    });
+    attempt(string("repeat").skip(space()))
+        .skip(spaces())
+        .with(optional(attempt(task_or_relative().skip(space()).skip(spaces()))).and(rule))
+        .map(|r| Repeat { rule: Some(r) })
+}
+
+/// `pause` stops the clock on the task in progress, `pause 2` on the given task
+fn pause<Input>() -> impl Parser<Input, Output = Pause>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("pause"))
+        .skip(spaces())
+        .with(optional(task_or_relative()))
+        .skip(spaces())
+        .skip(eof())
+        .map(|task| Pause { task })
+}
+
+/// `resume` starts the clock again on the task paused last, `resume 2` on the given task
+fn resume<Input>() -> impl Parser<Input, Output = Resume>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("resume"))
+        .skip(spaces())
+        .with(optional(task_or_relative()))
+        .skip(spaces())
+        .skip(eof())
+        .map(|task| Resume { task })
+}
+
+/// `1h30m`, `45m` or, to take time off, `-15m`
+fn duration<Input>() -> impl Parser<Input, Output = Duration>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let too_long = || {
+        StreamErrorFor::<Input>::message_format(format!(
+            "durations are at most {MAX_TIME_ENTRY_HOURS}h"
+        ))
+    };
+    let part =
+        many1(digit())
+            .and(char('h').or(char('m')))
+            .and_then(move |(n, unit): (String, char)| {
+                let minutes = n
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(if unit == 'h' { 60 } else { 1 }))
+                    .filter(|m| *m <= MAX_TIME_ENTRY_HOURS * 60)
+                    .ok_or_else(too_long)?;
+                Ok::<_, StreamErrorFor<Input>>(Duration::minutes(minutes))
+            });
+    optional(char('-'))
+        .and(many1::<Vec<_>, _, _>(part))
+        .and_then(move |(neg, parts)| {
+            let total = parts
+                .into_iter()
+                .try_fold(Duration::zero(), |t, p| t.checked_add(&p))
+                .filter(|t| *t <= Duration::hours(MAX_TIME_ENTRY_HOURS))
+                .ok_or_else(too_long)?;
+            Ok::<_, StreamErrorFor<Input>>(if neg.is_some() { -total } else { total })
+        })
+}
+
+/// `log 1h30m` adds time spent on the top task today, `log tsk-4 -15m yesterday` takes time off
+/// a task on another day
+fn log<Input>() -> impl Parser<Input, Output = Log>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("log").skip(space()))
+        .skip(spaces())
+        .with((
+            optional(attempt(task_or_relative().skip(space()).skip(spaces()))),
+            duration().skip(spaces()),
+            optional(day()),
+        ))
+        .skip(spaces())
+        .skip(eof())
+        .map(|t| Log { time: Some(t) })
+}
+
+fn relationship<Input>() -> impl Parser<Input, Output = (TaskOrRelative, String, TaskOrRelative)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        task_or_relative().skip(spaces()),
+        tag_name().skip(spaces()),
+        task_or_relative().skip(spaces()),
+    )
+        .skip(eof())
+}
+
+/// `connect tsk-1 blocks tsk-2`
+fn connect<Input>() -> impl Parser<Input, Output = Connect>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("connect"))
+        .skip(spaces())
+        .with(relationship())
+        .map(|r| Connect {
+            relationship: Some(r),
+        })
+}
+
+/// `disconnect tsk-1 blocks tsk-2`
+fn disconnect<Input>() -> impl Parser<Input, Output = Disconnect>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("disconnect"))
+        .skip(spaces())
+        .with(relationship())
+        .map(|r| Disconnect {
+            relationship: Some(r),
+        })
+}
+
 simple_parser!(edit, 'e', "edit", Edit);
 simple_parser!(drop, 'd', "drop", Drop);
 simple_parser!(complete, 'c', "complete", Complete);
 simple_parser!(quit, "quit", Quit);
+simple_parser!(undo, 'u', "undo", Undo);
+simple_parser!(backlog, 'b', "backlog", Backlog);
+simple_parser!(redo, "redo", Redo);
 simple_parser!(swap, "swap", Swap);
 simple_parser!(start, 's', "start", Start);
 simple_parser!(todo, 't', "todo", Todo);
 simple_parser!(rot, "rot", Rot);
 simple_parser!(nrot, '-', "-rot", NRot);
+simple_parser!(continue_focus, "continue", Continue);
+
+fn minutes<Input>() -> impl Parser<Input, Output = u16>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(digit()).and_then(|s: String| match s.parse::<u16>() {
+        Ok(minutes) if minutes > 0 => Ok(minutes),
+        _ => Err(StreamErrorFor::<Input>::message_format(format!(
+            "invalid number of minutes `{s}`"
+        ))),
+    })
+}
+
+/// `focus` starts the top task and a timer for `focus_minutes`, `focus 50` for 50 minutes
+fn focus<Input>() -> impl Parser<Input, Output = Focus>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("focus"))
+        .skip(spaces())
+        .with(optional(minutes()))
+        .skip(spaces())
+        .skip(eof())
+        .map(|minutes| Focus { minutes })
+}
+
+/// `break` pauses the focused task for `break_minutes`, `break 15` for 15 minutes
+fn take_break<Input>() -> impl Parser<Input, Output = Break>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("break"))
+        .skip(spaces())
+        .with(optional(minutes()))
+        .skip(spaces())
+        .skip(eof())
+        .map(|minutes| Break { minutes })
+}
 
 fn reprioritize<Input>() -> impl Parser<Input, Output = Reprioritize>
 where
@@ -192,12 +545,25 @@ pub(crate) enum HomeCommand {
     Rot(Rot),
     NRot(NRot),
     Make(Make),
+    Query(Query),
+    Open(Open),
+    Undo(Undo),
+    Redo(Redo),
+    Connect(Connect),
+    Disconnect(Disconnect),
+    Backlog(Backlog),
+    Markdown(Markdown),
+    Due(Due),
+    Schedule(Schedule),
+    Repeat(Repeat),
+    Pause(Pause),
+    Resume(Resume),
+    Log(Log),
+    Focus(Focus),
+    Continue(Continue),
+    Break(Break),
     /*
     New(New),
-    //Undo
-    Backlog(Backlog),
-    Connect(Option<(TaskId, Tag, TaskId)>),
-    Query(Query),
     Link(Link),
     Reprioritize(Reprioritize),
     Deprioritize(Deprioritize),
@@ -205,20 +571,223 @@ pub(crate) enum HomeCommand {
     */
 }
 
-enum DetailCommand {}
+simple_command!(Revisions);
+simple_command! {
+    Diff,
+    revisions -> (usize, Option<usize>)
+}
+simple_command! {
+    Restore,
+    revision -> usize
+}
+
+fn revision<Input>() -> impl Parser<Input, Output = usize>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    optional(char('#'))
+        .with(many1(digit()))
+        .and_then(|s: String| {
+            s.parse::<usize>().map_err(|_| {
+                StreamErrorFor::<Input>::message_format(format!("invalid revision `{s}`"))
+            })
+        })
+}
+
+simple_parser!(revisions, 'r', "revisions", Revisions);
+
+/// `diff 1 3` diffs revision 1 against 3, `diff 1` diffs it against the current revision.
+fn diff<Input>() -> impl Parser<Input, Output = Diff>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("diff"))
+        .skip(spaces())
+        .with(revision().skip(spaces()).and(optional(revision())))
+        .skip(spaces())
+        .skip(eof())
+        .map(|r| Diff { revisions: Some(r) })
+}
+
+fn restore<Input>() -> impl Parser<Input, Output = Restore>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(string("restore"))
+        .skip(spaces())
+        .with(revision())
+        .skip(spaces())
+        .skip(eof())
+        .map(|r| Restore { revision: Some(r) })
+}
+
+#[derive(Debug)]
+pub(crate) enum DetailCommand {
+    Revisions(Revisions),
+    Diff(Diff),
+    Restore(Restore),
+    Quit(Quit),
+}
+
+fn detail_command<Input>() -> impl Parser<Input, Output = DetailCommand>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        diff().map(DetailCommand::Diff),
+        restore().map(DetailCommand::Restore),
+        revisions().map(DetailCommand::Revisions),
+        quit().map(DetailCommand::Quit),
+    ))
+}
+
+simple_command! {
+    Sort,
+    order -> (BacklogOrder, bool)
+}
+simple_command! {
+    Restack,
+    task -> (Option<TaskOrRelative>, Option<usize>)
+}
+
+/// `sort created`, `sort status desc`
+fn sort<Input>() -> impl Parser<Input, Output = Sort>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let order = choice((
+        attempt(string("created")).map(|_| BacklogOrder::Created),
+        attempt(string("status")).map(|_| BacklogOrder::Status),
+    ));
+    let descending = optional(
+        attempt(string("desc"))
+            .map(|_| true)
+            .or(attempt(string("asc")).map(|_| false)),
+    )
+    .map(|d| d.unwrap_or(false));
+    attempt(string("sort"))
+        .skip(spaces())
+        .with(order.skip(spaces()).and(descending))
+        .skip(spaces())
+        .skip(eof())
+        .map(|o| Sort { order: Some(o) })
+}
+
+/// `push` pushes the selected task onto the stack, `push 3 at 2` pushes the third task of the
+/// backlog to the third place of the stack.
+fn restack<Input>() -> impl Parser<Input, Output = Restack>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let position = attempt(string("at"))
+        .skip(spaces())
+        .with(many1(digit()))
+        .and_then(|s: String| {
+            s.parse::<usize>().map_err(|_| {
+                StreamErrorFor::<Input>::message_format(format!("invalid position `{s}`"))
+            })
+        });
+    attempt(string("push"))
+        .or(char('p').map(|_| "push"))
+        .skip(spaces())
+        .with(
+            optional(task_or_relative())
+                .skip(spaces())
+                .and(optional(position)),
+        )
+        .skip(spaces())
+        .skip(eof())
+        .map(|t| Restack { task: Some(t) })
+}
+
+#[derive(Debug)]
+pub(crate) enum BacklogCommand {
+    Sort(Sort),
+    Push(Restack),
+    Open(Open),
+    Quit(Quit),
+}
+
+fn backlog_command<Input>() -> impl Parser<Input, Output = BacklogCommand>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        sort().map(BacklogCommand::Sort),
+        restack().map(BacklogCommand::Push),
+        open().map(BacklogCommand::Open),
+        quit().map(BacklogCommand::Quit),
+    ))
+}
+
+pub(crate) fn parse_backlog_command(input: &str) -> Option<BacklogCommand> {
+    let lower = input.to_ascii_lowercase();
+    let out = backlog_command()
+        .easy_parse(position::Stream::new(lower.as_str()))
+        .map(|c| c.0);
+    out.ok()
+}
+
+pub(crate) fn parse_detail_command(input: &str) -> Option<DetailCommand> {
+    let lower = input.to_ascii_lowercase();
+    let out = detail_command()
+        .easy_parse(position::Stream::new(lower.as_str()))
+        .map(|c| c.0);
+    out.ok()
+}
 
 #[derive(Debug)]
 pub(crate) enum CommandParseError {
     UnknownCommand(String),
     InvalidArgument(Vec<String>),
+    /// A query failed to parse at the given (1-based) column.
+    InvalidQuery(i32, String),
     Unknown,
 }
 
-enum TaskOrRelative {
+impl Display for CommandParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandParseError::UnknownCommand(c) => write!(f, "Unknown command {c}"),
+            CommandParseError::InvalidArgument(args) => {
+                write!(f, "Invalid argument(s): {}", args.join(", "))
+            }
+            CommandParseError::InvalidQuery(column, message) => {
+                write!(f, "Invalid query at column {column}: {message}")
+            }
+            CommandParseError::Unknown => write!(f, "Error parsing command"),
+        }
+    }
+}
+
+/// A task referenced either by its ID (`tsk-4`) or by its index in the home list (`2`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TaskOrRelative {
     Task(TaskId),
     Relative(u8),
 }
 
+fn task_or_relative<Input>() -> impl Parser<Input, Output = TaskOrRelative>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    tsk()
+        .map(TaskOrRelative::Task)
+        .or(many1(digit()).and_then(|s: String| {
+            s.parse::<u8>().map(TaskOrRelative::Relative).map_err(|_| {
+                StreamErrorFor::<Input>::message_format(format!("invalid index `{s}`"))
+            })
+        }))
+}
+
 fn str<Input>() -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
@@ -227,24 +796,165 @@ where
     between(char('"'), char('"'), many(satisfy(|c| c != '"')))
 }
 
+fn tag_name<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(alpha_num().or(char('_')).or(char('-')))
+}
+
+fn task_status<Input>() -> impl Parser<Input, Output = TaskStatus>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(letter().or(char('-'))).and_then(|s: String| {
+        s.parse::<TaskStatus>()
+            .map_err(|_| StreamErrorFor::<Input>::message_format(format!("unknown status `{s}`")))
+    })
+}
+
+/// `status:inprogress` or, negated, `-status:done`
+fn status_filter<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(optional(char('-')).skip(string("status:")))
+        .and(task_status())
+        .map(|(neg, status)| QueryArgs::Status(neg.is_some(), status))
+}
+
+/// `due:overdue`, `due:any`, `due:today` or `due:2026-11-01` for tasks due by the end of that
+/// day, `-due:any` for tasks without a due date
+fn due_filter<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(optional(char('-')).skip(string("due:")))
+        .and(choice((
+            attempt(string("any")).map(|_| DueFilter::Any),
+            attempt(string("overdue")).map(|_| DueFilter::Overdue),
+            day().map(DueFilter::By),
+        )))
+        .map(|(neg, filter)| QueryArgs::Due(neg.is_some(), filter))
+}
+
+/// `blocks:tsk-12` matches tasks that block TSK-12, `tsk-12:blocks` matches tasks TSK-12 blocks
+fn relation_filter<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        attempt(tsk().skip(char(':')).and(tag_name()))
+            .map(|(task_id, rel)| QueryArgs::Relation(rel, RelationshipSide::Left(task_id))),
+        attempt(tag_name().skip(char(':')).and(tsk()))
+            .map(|(rel, task_id)| QueryArgs::Relation(rel, RelationshipSide::Right(task_id))),
+    ))
+}
+
+/// `+work`, `#work` or `+#work` to include a tag, `-work` or `-#work` to exclude it
+fn tag_filter<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        char('+').map(|_| false),
+        char('-').map(|_| true),
+        look_ahead(char('#')).map(|_| false),
+    ))
+    .skip(optional(char('#')))
+    .and(tag_name())
+    .map(|(neg, tag)| QueryArgs::Tag(neg, tag.into()))
+}
+
+/// A quoted string or a bare word, both searched for in the title and body
+fn text_filter<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    str()
+        .or(many1(satisfy(|c: char| !c.is_whitespace() && c != '"')))
+        .map(QueryArgs::Text)
+}
+
+fn query_arg<Input>() -> impl Parser<Input, Output = QueryArgs>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        status_filter(),
+        due_filter(),
+        relation_filter(),
+        tag_filter(),
+        text_filter(),
+    ))
+    .skip(look_ahead(space().map(|_| ()).or(eof())))
+}
+
+fn query<Input>() -> impl Parser<Input, Output = Vec<QueryArgs>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    spaces().with(many(query_arg().skip(spaces()))).skip(eof())
+}
+
 fn command<Input>() -> impl Parser<Input, Output = HomeCommand>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     choice((
+        // before `complete`, `drop` and `start`, which accept their first letter alone
+        connect().map(HomeCommand::Connect),
+        disconnect().map(HomeCommand::Disconnect),
+        continue_focus().map(HomeCommand::Continue),
+        due().map(HomeCommand::Due),
+        schedule().map(HomeCommand::Schedule),
+        // before `push`, which would take "ause" as the title of a new task
+        pause().map(HomeCommand::Pause),
         push().map(HomeCommand::Push),
+        take_break().map(HomeCommand::Break),
+        backlog().map(HomeCommand::Backlog),
         edit().map(HomeCommand::Edit),
         drop().map(HomeCommand::Drop),
         complete().map(HomeCommand::Complete),
         swap().map(HomeCommand::Swap),
         start().map(HomeCommand::Start),
         todo().map(HomeCommand::Todo),
+        command_tail(),
+    ))
+}
+
+/// The rest of [`command`], split off as `choice` takes so many parsers at most.
+fn command_tail<Input>() -> impl Parser<Input, Output = HomeCommand>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        // before `make`
+        markdown().map(HomeCommand::Markdown),
         make().map(HomeCommand::Make),
-        // r
+        query_command().map(HomeCommand::Query),
+        open().map(HomeCommand::Open),
+        log().map(HomeCommand::Log),
+        focus().map(HomeCommand::Focus),
+        // r, `repeat` before `rep`
+        repeat().map(HomeCommand::Repeat),
+        resume().map(HomeCommand::Resume),
         rot().map(HomeCommand::Rot),
         nrot().map(HomeCommand::NRot),
+        redo().map(HomeCommand::Redo),
         reprioritize().map(HomeCommand::Reprioritize),
+        undo().map(HomeCommand::Undo),
         // quit
         quit().map(HomeCommand::Quit),
     ))
@@ -254,10 +964,72 @@ pub(crate) fn parse_home_command(input: &str) -> Option<HomeCommand> {
     let lower = input.to_ascii_lowercase();
     let out = command()
         .easy_parse(position::Stream::new(lower.as_str()))
-        .map(|c| c.0);
+        .map(|c| match c.0 {
+            // file names are case sensitive, take the name from the input as it was typed. It's
+            // at the end, and lowercasing ASCII doesn't change byte offsets.
+            HomeCommand::Markdown(Markdown {
+                file: Some((history, file)),
+            }) => {
+                let typed = input.trim_end();
+                HomeCommand::Markdown(Markdown {
+                    file: Some((history, typed[typed.len() - file.len()..].to_string())),
+                })
+            }
+            command => command,
+        });
+    out.ok()
+}
+
+/// `parse_task` parses a task reference given on the command line, `tsk-4` or a stack index.
+pub(crate) fn parse_task(input: &str) -> Option<TaskOrRelative> {
+    let lower = input.to_ascii_lowercase();
+    let out = task_or_relative()
+        .skip(eof())
+        .easy_parse(position::Stream::new(lower.as_str()))
+        .map(|t| t.0);
+    out.ok()
+}
+
+/// `parse_day` parses a day given on the command line, `today`, `tomorrow` or `2026-11-01`, or
+/// `none` for no day at all.
+pub(crate) fn parse_day(input: &str) -> Option<Option<Day>> {
+    let lower = input.to_ascii_lowercase();
+    let out = attempt(string("none"))
+        .map(|_| None)
+        .or(day().map(Some))
+        .skip(eof())
+        .easy_parse(position::Stream::new(lower.as_str()))
+        .map(|d| d.0);
     out.ok()
 }
 
+/// `parse_duration` parses a duration given on the command line, e.g. `1h30m` or `-15m`.
+pub(crate) fn parse_duration(input: &str) -> Option<Duration> {
+    let lower = input.to_ascii_lowercase();
+    let out = duration()
+        .skip(eof())
+        .easy_parse(position::Stream::new(lower.as_str()))
+        .map(|d| d.0);
+    out.ok()
+}
+
+/// `parse_query` parses the query language, e.g. `+work -#done status:inprogress "free text"
+/// blocks:tsk-12`, into the segments understood by `Db::query`.
+pub(crate) fn parse_query(input: &str) -> Result<Vec<QueryArgs>, CommandParseError> {
+    query()
+        .easy_parse(position::Stream::new(input))
+        .map(|q| q.0)
+        .map_err(|e| {
+            let message = e
+                .errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            CommandParseError::InvalidQuery(e.position.column, message)
+        })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -268,4 +1040,353 @@ mod test {
         let command = parse_home_command(input);
         assert!(command.is_some());
     }
+
+    #[test]
+    fn test_parse_open() {
+        for (input, expected) in [
+            ("open tsk-4", Some(TaskOrRelative::Task(4))),
+            ("o 2", Some(TaskOrRelative::Relative(2))),
+            ("open", None),
+        ] {
+            match parse_home_command(input) {
+                Some(HomeCommand::Open(o)) => assert_eq!(o.args().copied(), expected),
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+        assert!(parse_home_command("open 300").is_none());
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        for (input, expected) in [
+            ("markdown Notes/Week 12.md", (false, "Notes/Week 12.md")),
+            (
+                "markdown history /tmp/status.MD  ",
+                (true, "/tmp/status.MD"),
+            ),
+            ("markdown historyFile.md", (false, "historyFile.md")),
+        ] {
+            match parse_home_command(input) {
+                Some(HomeCommand::Markdown(m)) => {
+                    assert_eq!(m.args(), Some(&(expected.0, expected.1.to_string())))
+                }
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+        assert!(parse_home_command("markdown").is_none());
+        assert!(matches!(
+            parse_home_command("make urgent"),
+            Some(HomeCommand::Make(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_due() {
+        let date = Day::On(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap());
+        for (input, expected) in [
+            (
+                "due tsk-4 2026-11-01",
+                (Some(TaskOrRelative::Task(4)), Some(date)),
+            ),
+            (
+                "due 2 tomorrow",
+                (Some(TaskOrRelative::Relative(2)), Some(Day::Tomorrow)),
+            ),
+            ("due today ", (None, Some(Day::Today))),
+            ("due tsk-4 none", (Some(TaskOrRelative::Task(4)), None)),
+        ] {
+            match parse_home_command(input) {
+                Some(HomeCommand::Due(d)) => assert_eq!(d.args(), Some(&expected)),
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+        match parse_home_command("schedule 2026-11-01") {
+            Some(HomeCommand::Schedule(s)) => assert_eq!(s.args(), Some(&(None, Some(date)))),
+            other => panic!("unexpected command {other:?}"),
+        }
+        assert!(parse_home_command("due tsk-4").is_none());
+        assert!(parse_home_command("due 2026-13-01").is_none());
+        assert!(matches!(
+            parse_home_command("d"),
+            Some(HomeCommand::Drop(_))
+        ));
+        assert!(matches!(
+            parse_home_command("s"),
+            Some(HomeCommand::Start(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_repeat() {
+        for (input, expected) in [
+            (
+                "repeat tsk-4 weekly mon,thu",
+                (
+                    Some(TaskOrRelative::Task(4)),
+                    Some(Recurrence::Weekly(vec![
+                        chrono::Weekday::Mon,
+                        chrono::Weekday::Thu,
+                    ])),
+                ),
+            ),
+            (
+                "repeat every 10 days",
+                (None, Some(Recurrence::AfterDays(10))),
+            ),
+            ("repeat 2 none", (Some(TaskOrRelative::Relative(2)), None)),
+        ] {
+            match parse_home_command(input) {
+                Some(HomeCommand::Repeat(r)) => assert_eq!(r.args(), Some(&expected)),
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+        assert!(parse_home_command("repeat tsk-4 hourly").is_none());
+        assert!(matches!(
+            parse_home_command("rep tsk-4"),
+            Some(HomeCommand::Reprioritize(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_time_tracking() {
+        assert!(matches!(
+            parse_home_command("pause"),
+            Some(HomeCommand::Pause(Pause { task: None }))
+        ));
+        match parse_home_command("resume tsk-4") {
+            Some(HomeCommand::Resume(r)) => assert_eq!(r.args(), Some(&TaskOrRelative::Task(4))),
+            other => panic!("unexpected command {other:?}"),
+        }
+        for (input, expected) in [
+            ("log 1h30m", (None, Duration::minutes(90), None)),
+            (
+                "log tsk-4 -15m yesterday",
+                (
+                    Some(TaskOrRelative::Task(4)),
+                    Duration::minutes(-15),
+                    Some(Day::Yesterday),
+                ),
+            ),
+            (
+                "log 2 2h",
+                (Some(TaskOrRelative::Relative(2)), Duration::hours(2), None),
+            ),
+        ] {
+            match parse_home_command(input) {
+                Some(HomeCommand::Log(l)) => assert_eq!(l.args(), Some(&expected)),
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+        assert!(parse_home_command("log 90").is_none());
+        assert!(parse_home_command("log tsk-4").is_none());
+        assert_eq!(parse_duration("2H15m"), Some(Duration::minutes(135)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("-1000h"), Some(Duration::hours(-1000)));
+        assert_eq!(parse_duration("999h60m"), Some(Duration::hours(1000)));
+        for input in [
+            "1001h",
+            "999h61m",
+            "99999999999h",
+            "9999999999999999999999h",
+        ] {
+            assert_eq!(parse_duration(input), None, "parsed `{input}`");
+        }
+        assert!(matches!(
+            parse_home_command("p pause the music"),
+            Some(HomeCommand::Push(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_focus() {
+        assert!(matches!(
+            parse_home_command("focus"),
+            Some(HomeCommand::Focus(Focus { minutes: None }))
+        ));
+        assert!(matches!(
+            parse_home_command("Focus 50"),
+            Some(HomeCommand::Focus(Focus { minutes: Some(50) }))
+        ));
+        assert!(matches!(
+            parse_home_command("break 15"),
+            Some(HomeCommand::Break(Break { minutes: Some(15) }))
+        ));
+        assert!(matches!(
+            parse_home_command("continue"),
+            Some(HomeCommand::Continue(_))
+        ));
+        assert!(parse_home_command("focus 0").is_none());
+        assert!(parse_home_command("focus soon").is_none());
+        assert!(matches!(
+            parse_home_command("b"),
+            Some(HomeCommand::Backlog(_))
+        ));
+        assert!(matches!(
+            parse_home_command("c"),
+            Some(HomeCommand::Complete(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_task() {
+        assert_eq!(parse_task("TSK-12"), Some(TaskOrRelative::Task(12)));
+        assert_eq!(parse_task("3"), Some(TaskOrRelative::Relative(3)));
+        assert_eq!(parse_task("tsk-"), None);
+        assert_eq!(parse_task("3 4"), None);
+        assert_eq!(parse_task("tsk-99999999999999999999"), None);
+        // too large for a task ID, so it is searched for as text
+        assert!(parse_query("blocks:tsk-99999999999999999999").is_ok());
+    }
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(parse_day("Today"), Some(Some(Day::Today)));
+        assert_eq!(
+            parse_day("2026-11-01"),
+            Some(Some(Day::On(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap())))
+        );
+        assert_eq!(parse_day("none"), Some(None));
+        assert_eq!(parse_day("someday"), None);
+    }
+
+    #[test]
+    fn test_parse_detail_command() {
+        match parse_detail_command("diff 1 #3") {
+            Some(DetailCommand::Diff(d)) => assert_eq!(d.args(), Some(&(1, Some(3)))),
+            other => panic!("unexpected command {other:?}"),
+        }
+        match parse_detail_command("diff 2") {
+            Some(DetailCommand::Diff(d)) => assert_eq!(d.args(), Some(&(2, None))),
+            other => panic!("unexpected command {other:?}"),
+        }
+        match parse_detail_command("restore 2") {
+            Some(DetailCommand::Restore(r)) => assert_eq!(r.args(), Some(&2)),
+            other => panic!("unexpected command {other:?}"),
+        }
+        assert!(matches!(
+            parse_detail_command("r"),
+            Some(DetailCommand::Revisions(_))
+        ));
+        assert!(parse_detail_command("restore").is_none());
+        assert!(parse_detail_command("restore 99999999999999999999").is_none());
+    }
+
+    #[test]
+    fn test_parse_connect() {
+        match parse_home_command("connect tsk-1 blocks 2") {
+            Some(HomeCommand::Connect(c)) => assert_eq!(
+                c.args(),
+                Some(&(
+                    TaskOrRelative::Task(1),
+                    "blocks".to_string(),
+                    TaskOrRelative::Relative(2)
+                ))
+            ),
+            other => panic!("unexpected command {other:?}"),
+        }
+        assert!(matches!(
+            parse_home_command("disconnect tsk-1 blocks tsk-2"),
+            Some(HomeCommand::Disconnect(_))
+        ));
+        assert!(parse_home_command("connect tsk-1 blocks").is_none());
+    }
+
+    #[test]
+    fn test_parse_backlog_command() {
+        match parse_backlog_command("sort status desc") {
+            Some(BacklogCommand::Sort(s)) => {
+                assert_eq!(s.args(), Some(&(BacklogOrder::Status, true)))
+            }
+            other => panic!("unexpected command {other:?}"),
+        }
+        match parse_backlog_command("push tsk-4 at 2") {
+            Some(BacklogCommand::Push(p)) => {
+                assert_eq!(p.args(), Some(&(Some(TaskOrRelative::Task(4)), Some(2))))
+            }
+            other => panic!("unexpected command {other:?}"),
+        }
+        match parse_backlog_command("p") {
+            Some(BacklogCommand::Push(p)) => assert_eq!(p.args(), Some(&(None, None))),
+            other => panic!("unexpected command {other:?}"),
+        }
+        assert!(parse_backlog_command("sort title").is_none());
+        assert!(parse_backlog_command("push 1 at 99999999999999999999").is_none());
+    }
+
+    #[test]
+    fn test_parse_query_command() {
+        match parse_home_command("/ +work status:todo") {
+            Some(HomeCommand::Query(q)) => assert_eq!(q.args().map(Vec::len), Some(2)),
+            other => panic!("unexpected command {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query =
+            parse_query(r#"+work -#done status:inprogress "free text" blocks:tsk-12 #home"#)
+                .unwrap();
+        assert_eq!(
+            query,
+            vec![
+                QueryArgs::Tag(false, "work".to_string().into()),
+                QueryArgs::Tag(true, "done".to_string().into()),
+                QueryArgs::Status(false, TaskStatus::InProgress),
+                QueryArgs::Text("free text".to_string()),
+                QueryArgs::Relation("blocks".to_string(), RelationshipSide::Right(12)),
+                QueryArgs::Tag(false, "home".to_string().into()),
+            ]
+        );
+        assert_eq!(
+            parse_query("-status:done report").unwrap(),
+            vec![
+                QueryArgs::Status(true, TaskStatus::Complete),
+                QueryArgs::Text("report".to_string()),
+            ]
+        );
+        assert_eq!(parse_query("  ").unwrap(), vec![]);
+        assert_eq!(
+            parse_query("tsk-3:blocks").unwrap(),
+            vec![QueryArgs::Relation(
+                "blocks".to_string(),
+                RelationshipSide::Left(3)
+            )]
+        );
+        assert_eq!(
+            parse_query("due:overdue -due:any due:2026-11-01").unwrap(),
+            vec![
+                QueryArgs::Due(false, DueFilter::Overdue),
+                QueryArgs::Due(true, DueFilter::Any),
+                QueryArgs::Due(
+                    false,
+                    DueFilter::By(Day::On(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap()))
+                ),
+            ]
+        );
+        assert!(parse_query("due:someday").is_err());
+    }
+
+    #[test]
+    fn test_query_round_trip() {
+        let input = r#"+work -done status:inprogress -status:hidden "free text" blocks:tsk-12 tsk-3:blocks due:today -due:any"#;
+        let query = parse_query(input).unwrap();
+        let printed = query
+            .iter()
+            .map(|q| q.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(printed, input);
+    }
+
+    #[test]
+    fn test_parse_query_error_column() {
+        match parse_query("+work status:bogus") {
+            Err(CommandParseError::InvalidQuery(column, _)) => assert_eq!(column, 14),
+            other => panic!("unexpected result {other:?}"),
+        }
+        match parse_query("+work \"unterminated") {
+            Err(CommandParseError::InvalidQuery(column, _)) => assert_eq!(column, 20),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
 }
@@ -1,17 +1,320 @@
-const APP_PREFIX: &'static str = "tsk";
-const DATABASE: &'static str = "tsk.db";
-const CONFIG: &'static str = "config.toml";
+#![allow(dead_code)]
+const APP_PREFIX: &str = "tsk";
+const DATABASE: &str = "tsk.db";
+const CONFIG: &str = "config.toml";
+/// The `priority_task` view only walks this many tasks down the stack.
+pub(crate) const MAX_TOP_TASKS: u16 = 20;
 use crate::error::{Error, Result};
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
-use xdg;
 
+/// Written to the config directory the first time tsk runs, so every setting is discoverable.
+const DEFAULT_CONFIG: &str = r##"# tsk configuration. Every setting is optional; the commented values are the defaults.
+
+# How many tasks from the top of the stack the home view shows (1-20).
+# num_top_tasks = 10
+
+# Where the database lives. Must be an absolute path, `~/` is expanded.
+# Defaults to $XDG_STATE_HOME/tsk/tsk.db.
+# database = "~/.local/state/tsk/tsk.db"
+
+# Due dates this many days away or closer are highlighted in the home view, 0 to only
+# highlight overdue tasks.
+# due_soon_days = 3
+
+# Where the next instance of a repeating task goes on the stack when the current one is
+# completed, 0 being the top. Positions past the bottom of the stack append to it.
+# recurring_position = 0
+
+# How many minutes `focus` and `break` run for when no length is given.
+# focus_minutes = 25
+# break_minutes = 5
+
+# strftime format used whenever a date is shown.
+# date_format = "%Y-%m-%d %H:%M"
+
+# Built-in color theme: "dark", "light" or "high-contrast".
+# theme = "dark"
+
+# Overrides for the theme's styles. A style is an optional color, an optional background
+# ("on <color>") and any of bold, dim, italic, underlined, reversed, crossed_out and blink.
+# Colors are names such as "white", "darkgray" and "lightblue", or hex values like "#ff8800".
+[colors]
+# text = "white"
+# muted = "darkgray"
+# error = "red"
+# highlight = "yellow bold"
+# selected = "reversed"
+# added = "green"
+# removed = "red"
+# tag = "cyan"
+# overdue = "red bold"
+# due_soon = "yellow"
+# focus = "green"
+
+# Styles for tasks by status: todo, inprogress, complete, cancelled and hidden.
+[colors.status]
+# inprogress = "yellow"
+# complete = "green"
+# cancelled = "darkgray crossed_out"
+
+# Styles for individual tags, replacing the `tag` style.
+[colors.tags]
+# urgent = "red bold"
+
+# Keys are bound to actions separately for each view: home, details, backlog and query.
+# An action takes one key or a list of keys, such as "esc", "ctrl-q", "alt-x" or "f5". Setting
+# an action replaces all of its default keys, an empty list unbinds it. A key can only be bound
+# to one action per view, and letters need ctrl or alt since they're typed into the editor.
+[keys.home]
+# quit = ["esc", "ctrl-q"]
+# complete_top = "ctrl-t"
+# swap = "ctrl-s"
+# open_details = "ctrl-o"
+# open_query = "ctrl-g"
+# undo = "ctrl-z"
+
+[keys.details]
+# back = "esc"
+# quit = "ctrl-q"
+
+[keys.backlog]
+# back = "esc"
+# quit = "ctrl-q"
+# open_details = "ctrl-o"
+
+[keys.query]
+# back = "esc"
+# quit = "ctrl-q"
+
+# Aliases replace the first word of a command before it is parsed.
+[aliases]
+# done = "complete"
+# top = "push"
+"##;
+
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Config {
     pub num_top_tasks: u16,
+    /// Tasks due within this many days are highlighted as due soon
+    pub due_soon_days: u16,
+    /// The stack position new instances of repeating tasks are inserted at
+    pub recurring_position: usize,
+    /// The default length of a focus session
+    pub focus_minutes: u16,
+    /// The default length of a break after one
+    pub break_minutes: u16,
+    /// Overrides the XDG state directory location of the database
+    pub database: Option<PathBuf>,
+    pub date_format: String,
+    pub theme: Theme,
+    /// Maps a word to the command text it expands to, e.g. `done = "complete"`
+    pub aliases: HashMap<String, String>,
+    pub keys: Keymap,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { num_top_tasks: 10 }
+        Self {
+            num_top_tasks: 10,
+            due_soon_days: 3,
+            recurring_position: 0,
+            focus_minutes: 25,
+            break_minutes: 5,
+            database: None,
+            date_format: "%Y-%m-%d %H:%M".to_string(),
+            theme: Theme::default(),
+            aliases: HashMap::new(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+/// The config file as written by the user, before validation. Anything left out falls back to
+/// [`Config::default`].
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    num_top_tasks: Option<u16>,
+    due_soon_days: Option<u16>,
+    recurring_position: Option<usize>,
+    focus_minutes: Option<u16>,
+    break_minutes: Option<u16>,
+    database: Option<String>,
+    date_format: Option<String>,
+    theme: Option<String>,
+    colors: ColorsFile,
+    aliases: HashMap<String, String>,
+    keys: HashMap<String, HashMap<String, KeysFile>>,
+}
+
+/// Actions can be bound to a single key or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a key or a list of keys")]
+enum KeysFile {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Style overrides for the theme. UI elements are validated by [`Theme::new`].
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ColorsFile {
+    status: HashMap<String, String>,
+    tags: HashMap<String, String>,
+    #[serde(flatten)]
+    elements: HashMap<String, String>,
+}
+
+impl TryFrom<ConfigFile> for Config {
+    type Error = Error;
+
+    fn try_from(file: ConfigFile) -> Result<Self> {
+        let defaults = Config::default();
+        let num_top_tasks = file.num_top_tasks.unwrap_or(defaults.num_top_tasks);
+        if !(1..=MAX_TOP_TASKS).contains(&num_top_tasks) {
+            return Err(Error::InvalidConfig(format!(
+                "num_top_tasks must be between 1 and {MAX_TOP_TASKS}, got {num_top_tasks}"
+            )));
+        }
+        let minutes = |name, value: Option<u16>, default| match value.unwrap_or(default) {
+            0 => Err(Error::InvalidConfig(format!("{name} must be at least 1"))),
+            minutes => Ok(minutes),
+        };
+        let focus_minutes = minutes("focus_minutes", file.focus_minutes, defaults.focus_minutes)?;
+        let break_minutes = minutes("break_minutes", file.break_minutes, defaults.break_minutes)?;
+        let database = match file.database {
+            None => None,
+            Some(path) => {
+                let path = match path.strip_prefix("~/") {
+                    Some(rest) => std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(rest))
+                        .ok_or_else(|| {
+                            Error::InvalidConfig("database: $HOME is not set".to_string())
+                        })?,
+                    None => PathBuf::from(path),
+                };
+                if !path.is_absolute() {
+                    return Err(Error::InvalidConfig(format!(
+                        "database must be an absolute path, got \"{}\"",
+                        path.display()
+                    )));
+                }
+                Some(path)
+            }
+        };
+        let date_format = file.date_format.unwrap_or(defaults.date_format);
+        if StrftimeItems::new(&date_format).any(|item| item == Item::Error) {
+            return Err(Error::InvalidConfig(format!(
+                "date_format \"{date_format}\" is not a valid strftime format"
+            )));
+        }
+        let theme = Theme::new(
+            file.theme.as_deref().unwrap_or("dark"),
+            &file.colors.elements,
+            &file.colors.status,
+            &file.colors.tags,
+        )?;
+        for (alias, expansion) in &file.aliases {
+            if alias.is_empty() || alias.contains(char::is_whitespace) {
+                return Err(Error::InvalidConfig(format!(
+                    "aliases: \"{alias}\" must be a single word"
+                )));
+            }
+            if expansion.trim().is_empty() {
+                return Err(Error::InvalidConfig(format!(
+                    "aliases: \"{alias}\" expands to nothing"
+                )));
+            }
+        }
+        // commands are matched case insensitively, so aliases are too
+        let aliases = file
+            .aliases
+            .into_iter()
+            .map(|(alias, expansion)| (alias.to_lowercase(), expansion))
+            .collect();
+        let keys = file
+            .keys
+            .into_iter()
+            .map(|(view, actions)| {
+                let actions = actions
+                    .into_iter()
+                    .map(|(action, keys)| match keys {
+                        KeysFile::One(key) => (action, vec![key]),
+                        KeysFile::Many(keys) => (action, keys),
+                    })
+                    .collect();
+                (view, actions)
+            })
+            .collect();
+        Ok(Config {
+            num_top_tasks,
+            due_soon_days: file.due_soon_days.unwrap_or(defaults.due_soon_days),
+            recurring_position: file
+                .recurring_position
+                .unwrap_or(defaults.recurring_position),
+            focus_minutes,
+            break_minutes,
+            database,
+            date_format,
+            theme,
+            aliases,
+            keys: Keymap::new(&keys)?,
+        })
+    }
+}
+
+impl Config {
+    /// `load` reads the config file, writing a commented default one first if there is none.
+    pub(super) fn load() -> Result<Config> {
+        let path = get_config_file()?;
+        if !path.exists() {
+            fs::write(&path, DEFAULT_CONFIG)?;
+        }
+        let contents = fs::read_to_string(&path)?;
+        Config::parse(&contents).map_err(|e| match e {
+            Error::InvalidConfig(e) => {
+                Error::InvalidConfig(format!("{}: {}", path.display(), e.trim_end()))
+            }
+            e => e,
+        })
+    }
+
+    fn parse(contents: &str) -> Result<Config> {
+        let file: ConfigFile =
+            toml::from_str(contents).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+        file.try_into()
+    }
+
+    /// `database_file` is the configured database path, or the default one in the XDG state
+    /// directory.
+    pub(super) fn database_file(&self) -> Result<PathBuf> {
+        match &self.database {
+            Some(path) => Ok(path.clone()),
+            None => get_database_file(),
+        }
+    }
+
+    /// `expand_alias` replaces the first word of `input` if it is a configured alias.
+    pub(crate) fn expand_alias<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let trimmed = input.trim_start();
+        let (word, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .map(|(word, rest)| (word, Some(rest)))
+            .unwrap_or((trimmed, None));
+        match self.aliases.get(&word.to_lowercase()) {
+            Some(expansion) => match rest {
+                Some(rest) => Cow::Owned(format!("{expansion} {rest}")),
+                None => Cow::Owned(expansion.clone()),
+            },
+            None => Cow::Borrowed(input),
+        }
     }
 }
 
@@ -23,10 +326,116 @@ impl From<xdg::BaseDirectoriesError> for Error {
 
 pub(super) fn get_database_file() -> Result<PathBuf> {
     let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_PREFIX)?;
-    Ok(xdg_dirs.place_state_file(&DATABASE)?)
+    Ok(xdg_dirs.place_state_file(DATABASE)?)
 }
 
 pub fn get_config_file() -> Result<PathBuf> {
     let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_PREFIX)?;
-    Ok(xdg_dirs.place_config_file(&CONFIG)?)
+    Ok(xdg_dirs.place_config_file(CONFIG)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keymap::Action;
+    use crate::types::TaskStatus;
+    use ratatui::style::{Color, Modifier, Style};
+    use tui_textarea::{Input, Key};
+
+    fn error(contents: &str) -> String {
+        match Config::parse(contents) {
+            Err(Error::InvalidConfig(e)) => e,
+            other => panic!("expected a config error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_default_config_file() {
+        assert_eq!(Config::parse(DEFAULT_CONFIG).unwrap(), Config::default());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r##"
+            num_top_tasks = 5
+            due_soon_days = 7
+            recurring_position = 3
+            focus_minutes = 50
+            database = "/tmp/tsk.db"
+            date_format = "%d/%m/%Y"
+            theme = "light"
+            [colors]
+            muted = "#808080"
+            error = "lightred bold"
+            [colors.status]
+            complete = "green"
+            [aliases]
+            Done = "complete"
+            [keys.home]
+            undo = ["ctrl-u", "f2"]
+            quit = "ctrl-q"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.num_top_tasks, 5);
+        assert_eq!(config.due_soon_days, 7);
+        assert_eq!(config.recurring_position, 3);
+        assert_eq!(config.focus_minutes, 50);
+        assert_eq!(config.break_minutes, 5);
+        assert_eq!(config.database, Some(PathBuf::from("/tmp/tsk.db")));
+        assert_eq!(config.date_format, "%d/%m/%Y");
+        assert_eq!(config.theme.text, Theme::light().text);
+        assert_eq!(
+            config.theme.muted,
+            Style::default().fg(Color::Rgb(0x80, 0x80, 0x80))
+        );
+        assert_eq!(
+            config.theme.error,
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(
+            config.theme.status(TaskStatus::Complete),
+            Style::default().fg(Color::Green)
+        );
+        assert_eq!(config.expand_alias("done 2"), "complete 2");
+        assert_eq!(config.expand_alias("DONE"), "complete");
+        assert_eq!(config.expand_alias("donE2"), "donE2");
+        assert_eq!(config.expand_alias("push done"), "push done");
+        let key = |key| Input {
+            key,
+            ctrl: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.keys.home.action(&key(Key::Char('u'))),
+            Some(Action::Undo)
+        );
+        assert_eq!(config.keys.home.action(&key(Key::Char('z'))), None);
+        assert_eq!(
+            config.keys.home.action(&key(Key::Char('q'))),
+            Some(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(error("num_top_tasks = 0").contains("between 1 and 20"));
+        assert!(error("num_top_tasks = 21").contains("between 1 and 20"));
+        assert!(error("num_top_tasks = \"ten\"").contains("num_top_tasks"));
+        assert!(error("due_soon_days = -1").contains("due_soon_days"));
+        assert!(error("break_minutes = 0").contains("break_minutes"));
+        assert!(error("database = \"tsk.db\"").contains("absolute path"));
+        assert!(error("date_format = \"%Q\"").contains("strftime"));
+        assert!(error("[colors]\ntext = \"blurple\"").contains("colors.text"));
+        assert!(error("theme = \"solarized\"").contains("unknown theme"));
+        assert!(error("[aliases]\n\"a b\" = \"push\"").contains("single word"));
+        assert!(error("[aliases]\nx = \" \"").contains("expands to nothing"));
+        assert!(error("num_top_task = 5").contains("unknown field"));
+        assert!(error("[keys.home]\nswap = \"ctrl-z\"").contains("bound to both"));
+        assert!(error("[keys.home]\nswap = 5").contains("a key or a list of keys"));
+    }
 }
@@ -1,12 +1,26 @@
 #![allow(dead_code)]
 use crate::{
-    config::get_database_file,
+    dump::{
+        Dump, DumpRelationship, DumpRevision, DumpStatus, DumpTask, DumpTimeEntry, ImportMode,
+        DUMP_VERSION,
+    },
     error::{Error, Result},
-    types::{QueryArgs, Task, TaskContent, TaskId, TaskStatus},
+    recurrence::Recurrence,
+    types::{
+        local_date, start_of_day, BacklogOrder, Day, DueFilter, QueryArgs, Relationship,
+        RelationshipSide, Revision, SearchResult, StatusChange, Tag, Task, TaskContent, TaskId,
+        TaskStatus, WorkInterval,
+    },
 };
-use chrono::DateTime;
+use chrono::{DateTime, Days, Duration, Local, Utc};
 use log::debug;
-use rusqlite::{Connection, Error as SQLiteError, OptionalExtension, Transaction};
+use rusqlite::{
+    params_from_iter, types::Value, Connection, Error as SQLiteError, OptionalExtension,
+    Transaction,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use uris::Uri;
 
 impl From<SQLiteError> for Error {
@@ -15,7 +29,11 @@ impl From<SQLiteError> for Error {
     }
 }
 
-const INITIALIZE: &'static str = "
+/// `MIGRATIONS` holds every schema change in order. Migration `i` takes a database from
+/// `user_version` `i` to `i + 1`, so existing entries must never be edited, only appended to.
+const MIGRATIONS: [&str; 10] = [
+    // 1: initial schema
+    "
 CREATE TABLE IF NOT EXISTS TAG (
     NAME TEXT NOT NULL UNIQUE,
     PRIMARY KEY(NAME)
@@ -84,38 +102,178 @@ CREATE VIEW IF NOT EXISTS top_tasks(id, title, status, next) AS
   JOIN TASK_STATUS ON priority_task.ID = task_status.TASK_ID
   GROUP BY task_status.TASK_ID
   HAVING MAX(task_status.UPDATED);
+",
+    // 2: full-text search over titles and bodies
+    "
+CREATE VIRTUAL TABLE IF NOT EXISTS TASK_FTS USING fts5(
+    TITLE,
+    BODY,
+    tokenize = 'porter unicode61'
+);
+CREATE TRIGGER IF NOT EXISTS TASK_FTS_INSERT AFTER INSERT ON TASK BEGIN
+    INSERT INTO TASK_FTS(ROWID, TITLE, BODY) VALUES (new.ID, new.TITLE, '');
+END;
+CREATE TRIGGER IF NOT EXISTS TASK_FTS_UPDATE AFTER UPDATE OF TITLE ON TASK BEGIN
+    UPDATE TASK_FTS SET TITLE = new.TITLE WHERE ROWID = new.ID;
+END;
+CREATE TRIGGER IF NOT EXISTS TASK_FTS_DELETE AFTER DELETE ON TASK BEGIN
+    DELETE FROM TASK_FTS WHERE ROWID = old.ID;
+END;
+-- The newest TASK_CONTENT row is always the current content
+CREATE TRIGGER IF NOT EXISTS TASK_FTS_CONTENT AFTER INSERT ON TASK_CONTENT BEGIN
+    UPDATE TASK_FTS SET BODY = coalesce(new.BODY, '') WHERE ROWID = new.TASK_ID;
+END;
+-- Index tasks created before the index existed
+INSERT INTO TASK_FTS(ROWID, TITLE, BODY)
+    SELECT ID, TITLE, '' FROM TASK
+    WHERE ID > 0 AND ID NOT IN (SELECT ROWID FROM TASK_FTS);
+",
+    // 3: BODY and RELATIONSHIP.TAG were declared INTEGER, which STRICT tables refuse text for
+    "
+CREATE TABLE TASK_CONTENT_NEW (
+    TASK_ID INTEGER NOT NULL,
+    BODY TEXT,
+    LINK TEXT,
+    UPDATED INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') as INT)),
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(UPDATED, TASK_ID)
+) STRICT;
+INSERT INTO TASK_CONTENT_NEW(TASK_ID, BODY, LINK, UPDATED)
+    SELECT TASK_ID, CAST(BODY AS TEXT), LINK, UPDATED FROM TASK_CONTENT;
+DROP TABLE TASK_CONTENT;
+ALTER TABLE TASK_CONTENT_NEW RENAME TO TASK_CONTENT;
+CREATE TRIGGER IF NOT EXISTS TASK_FTS_CONTENT AFTER INSERT ON TASK_CONTENT BEGIN
+    UPDATE TASK_FTS SET BODY = coalesce(new.BODY, '') WHERE ROWID = new.TASK_ID;
+END;
 
-PRAGMA user_version = 1;
-";
+CREATE TABLE RELATIONSHIP_NEW (
+    LEFT INTEGER NOT NULL,
+    TAG TEXT NOT NULL,
+    RIGHT INTEGER NOT NULL,
+    FOREIGN KEY(LEFT) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(LEFT, TAG, RIGHT),
+    FOREIGN KEY(TAG) REFERENCES TAG(NAME) ON DELETE CASCADE,
+    FOREIGN KEY(RIGHT) REFERENCES TASK(ID) ON DELETE CASCADE
+) STRICT;
+INSERT INTO RELATIONSHIP_NEW(LEFT, TAG, RIGHT)
+    SELECT LEFT, CAST(TAG AS TEXT), RIGHT FROM RELATIONSHIP;
+DROP TABLE RELATIONSHIP;
+ALTER TABLE RELATIONSHIP_NEW RENAME TO RELATIONSHIP;
+",
+    // 4: undo journal
+    "
+CREATE TABLE UNDO_OPERATION (
+    ID INTEGER NOT NULL UNIQUE,
+    NAME TEXT NOT NULL,
+    UNDONE INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(ID AUTOINCREMENT)
+) STRICT;
+CREATE TABLE UNDO_ENTRY (
+    OPERATION_ID INTEGER NOT NULL,
+    SEQ INTEGER NOT NULL,
+    KIND INTEGER NOT NULL,
+    TASK_ID INTEGER NOT NULL,
+    OLD INTEGER,
+    NEW INTEGER,
+    UPDATED INTEGER,
+    TITLE TEXT,
+    FOREIGN KEY(OPERATION_ID) REFERENCES UNDO_OPERATION(ID) ON DELETE CASCADE,
+    PRIMARY KEY(OPERATION_ID, SEQ)
+) STRICT;
+",
+    // 5: free-form key:value metadata, e.g. from todo.txt
+    "
+CREATE TABLE TASK_METADATA (
+    TASK_ID INTEGER NOT NULL,
+    KEY TEXT NOT NULL,
+    VALUE TEXT NOT NULL,
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(TASK_ID, KEY)
+) STRICT;
+",
+    // 6: due and scheduled dates
+    "
+CREATE TABLE TASK_DATE (
+    TASK_ID INTEGER NOT NULL UNIQUE,
+    DUE INTEGER,
+    SCHEDULED INTEGER,
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(TASK_ID)
+) STRICT;
+",
+    // 7: recurrence rules
+    "
+CREATE TABLE TASK_RECURRENCE (
+    TASK_ID INTEGER NOT NULL UNIQUE,
+    RULE TEXT NOT NULL,
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(TASK_ID)
+) STRICT;
+",
+    // 8: manual time entries, on top of the time tracked from TASK_STATUS
+    "
+CREATE TABLE TIME_ENTRY (
+    ID INTEGER NOT NULL UNIQUE,
+    TASK_ID INTEGER NOT NULL,
+    STARTED INTEGER NOT NULL,
+    SECONDS INTEGER NOT NULL,
+    CREATED INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') as INT)),
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(ID AUTOINCREMENT)
+) STRICT;
+",
+    // 9: finished focus sessions
+    "
+CREATE TABLE FOCUS_SESSION (
+    ID INTEGER NOT NULL UNIQUE,
+    TASK_ID INTEGER NOT NULL,
+    STARTED INTEGER NOT NULL,
+    SECONDS INTEGER NOT NULL,
+    FOREIGN KEY(TASK_ID) REFERENCES TASK(ID) ON DELETE CASCADE,
+    PRIMARY KEY(ID AUTOINCREMENT)
+) STRICT;
+",
+    // 10: what undoing a task's creation deleted, for redo to put back
+    "
+ALTER TABLE UNDO_ENTRY ADD COLUMN SNAPSHOT TEXT;
+",
+];
 
 pub(super) struct Db {
     conn: Connection,
 }
 
 impl Db {
-    pub(super) fn new() -> Result<Db> {
-        let db_path = get_database_file()?;
+    pub(super) fn new(db_path: &Path) -> Result<Db> {
         debug!("Opening databases at {db_path:?}");
-        let conn = Connection::open(db_path)?;
-        debug!("Database connection opened, initializing...");
-        Self::initialize(&conn)?;
-        debug!("Database initialized.");
+        let mut conn = Connection::open(db_path)?;
+        debug!("Database connection opened, migrating...");
+        migrate(&mut conn)?;
+        debug!("Database migrated.");
         Ok(Db { conn })
     }
 
-    fn initialize(conn: &Connection) -> Result<()> {
-        conn.execute_batch(INITIALIZE)?;
-        Ok(())
+    #[cfg(test)]
+    pub(super) fn in_memory() -> Result<Db> {
+        let mut conn = Connection::open_in_memory()?;
+        migrate(&mut conn)?;
+        Ok(Db { conn })
     }
 
     pub(super) fn create_task(&mut self, title: String) -> Result<TaskId> {
         let tx = self.conn.transaction()?;
-        tx.execute("INSERT INTO TASK(TITLE) VALUES(?)", (title,))?;
-        let row_id = tx.last_insert_rowid();
-        let task_id = tx.query_row("SELECT ID FROM TASK WHERE ROWID = ?", (row_id,), |row| {
-            row.get(0)
-        })?;
-        update_status(&tx, task_id, TaskStatus::Todo)?;
+        begin_operation(&tx, "create")?;
+        let task_id = create_task(&tx, title)?;
+        tx.commit()?;
+        Ok(task_id)
+    }
+
+    /// `push_task` creates a task and puts it at the top of the stack.
+    pub(super) fn push_task(&mut self, title: String) -> Result<TaskId> {
+        let tx = self.conn.transaction()?;
+        begin_operation(&tx, "push")?;
+        let task_id = create_task(&tx, title)?;
+        set_next_of(&tx, task_id, 0)?;
         tx.commit()?;
         Ok(task_id)
     }
@@ -127,80 +285,406 @@ impl Db {
         link: Option<String>,
     ) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO TASK_CONTENT(TASK_ID, BODY, LINK) VALUES(?, ?, ?)",
+            &format!(
+                "INSERT INTO TASK_CONTENT(TASK_ID, BODY, LINK, UPDATED) VALUES(?1, ?2, ?3, {NEXT_REVISION})"
+            ),
             (task_id, body, link),
         )?;
         Ok(())
     }
 
+    /// `get_revisions` returns every version of the content of `task_id`, oldest first.
+    pub(super) fn get_revisions(&self, task_id: TaskId) -> Result<Vec<Revision>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT UPDATED, BODY, LINK FROM TASK_CONTENT WHERE TASK_ID = ? ORDER BY UPDATED",
+        )?;
+        let revisions = stmt
+            .query_map((task_id,), |row| {
+                Ok(Revision {
+                    updated: DateTime::from_timestamp(row.get(0)?, 0)
+                        .or(DateTime::from_timestamp(0, 0))
+                        .unwrap(),
+                    content: TaskContent {
+                        body: row.get(1)?,
+                        link: row
+                            .get::<_, Option<String>>(2)?
+                            .and_then(|link| Uri::parse(link).ok()),
+                    },
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(revisions)
+    }
+
+    /// `restore_revision` makes the revision of `task_id` from `updated` the current content by
+    /// copying it into a new revision, so nothing in between is lost.
+    pub(super) fn restore_revision(&self, task_id: TaskId, updated: DateTime<Utc>) -> Result<()> {
+        let restored = self.conn.execute(
+            &format!(
+                "INSERT INTO TASK_CONTENT(TASK_ID, BODY, LINK, UPDATED)
+                SELECT TASK_ID, BODY, LINK, {NEXT_REVISION}
+                FROM TASK_CONTENT WHERE TASK_ID = ?1 AND UPDATED = ?2"
+            ),
+            (task_id, updated.timestamp()),
+        )?;
+        if restored == 0 {
+            return Err(Error::Database(format!(
+                "TSK-{task_id} has no revision from {updated}"
+            )));
+        }
+        Ok(())
+    }
+
     pub(super) fn update_status(&mut self, task_id: u64, state: TaskStatus) -> Result<()> {
         let tx = self.conn.transaction()?;
+        if matches!(state, TaskStatus::InProgress | TaskStatus::Complete) {
+            check_not_blocked(&tx, task_id)?;
+        }
+        begin_operation(&tx, state.name())?;
         update_status(&tx, task_id, state)?;
         tx.commit()?;
         Ok(())
     }
 
     pub(super) fn get_task(&self, task_id: u64) -> Result<Task> {
-        let status_int: u8 = self.conn.query_row(
-            "SELECT STATE FROM TASK_STATUS WHERE TASK_ID = ? HAVING MAX(UPDATED)",
-            (task_id,),
-            |row| row.get(0),
-        )?;
+        let status_int: u8 = self
+            .conn
+            .query_row(
+                "SELECT STATUS FROM TASK_STATUS
+                WHERE TASK_ID = ?
+                GROUP BY TASK_ID HAVING MAX(UPDATED)",
+                (task_id,),
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or_default();
         let task_status: TaskStatus = status_int.try_into()?;
-        let mut task = self.conn.query_row(
-            "SELECT TASK(TITLE, CREATED) WHERE TASK_ID = ?",
-            (task_id,),
-            |row| {
-                Ok(Task::new(
-                    task_id,
-                    task_status,
-                    row.get(0)?,
-                    DateTime::from_timestamp(row.get(1)?, 0)
-                        .or(DateTime::from_timestamp(0, 0))
-                        .unwrap(),
-                ))
-            },
+        let mut task = self
+            .conn
+            .query_row(
+                "SELECT TITLE, CREATED FROM TASK WHERE ID = ?",
+                (task_id,),
+                |row| {
+                    Ok(Task::new(
+                        task_id,
+                        task_status,
+                        row.get(0)?,
+                        DateTime::from_timestamp(row.get(1)?, 0)
+                            .or(DateTime::from_timestamp(0, 0))
+                            .unwrap(),
+                    ))
+                },
+            )
+            .optional()?
+            .ok_or_else(|| Error::Database(format!("TSK-{task_id} does not exist")))?;
+        let content: Option<TaskContent> = self
+            .conn
+            .query_row(
+                "SELECT BODY, LINK FROM TASK_CONTENT
+                WHERE TASK_ID = ?
+                GROUP BY TASK_ID HAVING MAX(UPDATED)",
+                (task_id,),
+                |row| {
+                    let link = if let Some(link) = row.get(1)? {
+                        // if the string fails to parse, we just drop it
+                        Uri::parse::<String>(link).ok()
+                    } else {
+                        None
+                    };
+                    Ok(TaskContent {
+                        body: row.get(0)?,
+                        link,
+                    })
+                },
+            )
+            .optional()?;
+        if let Some(content) = content {
+            if content.body.is_some() || content.link.is_some() {
+                task.content = Some(content);
+            }
+        }
+        task.tags = self.get_tags(task_id)?;
+        task.relationships = self.get_relationships(task_id)?;
+        task.metadata = self.get_metadata(task_id)?;
+        (task.due, task.scheduled) = dates(&self.conn, task_id)?;
+        task.recurrence = recurrence(&self.conn, task_id)?;
+        Ok(task)
+    }
+
+    /// `get_status_history` returns every status transition of `task_id`, oldest first. Repeated
+    /// rows with the same status are folded into the first one.
+    pub(super) fn get_status_history(&self, task_id: TaskId) -> Result<Vec<StatusChange>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT STATUS, UPDATED FROM TASK_STATUS WHERE TASK_ID = ? ORDER BY UPDATED",
         )?;
-        let content: TaskContent = self.conn.query_row(
-            "SELECT BODY, LINK FROM TASK_CONTENT
-            WHERE TASK_ID = ?
-            GROUP BY TASK_ID HAVING MAX(UPDATED)",
-            (task_id,),
-            |row| {
-                let link = if let Some(link) = row.get(1)? {
-                    // if the string fails to parse, we just drop it
-                    Uri::parse::<String>(link).ok()
-                } else {
-                    None
-                };
-                Ok(TaskContent {
-                    body: row.get(0)?,
-                    link,
+        let mut rows = stmt.query((task_id,))?;
+        let mut out: Vec<StatusChange> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let status_int: u8 = row.get(0)?;
+            let status: TaskStatus = status_int.try_into()?;
+            let updated = DateTime::from_timestamp(row.get(1)?, 0)
+                .or(DateTime::from_timestamp(0, 0))
+                .unwrap();
+            match out.last_mut() {
+                Some(last) if last.status == status => continue,
+                Some(last) => last.until = Some(updated),
+                None => {}
+            }
+            out.push(StatusChange {
+                status,
+                updated,
+                until: None,
+            });
+        }
+        Ok(out)
+    }
+
+    /// `get_relationships` returns every relationship `task_id` takes part in, from either side.
+    pub(super) fn get_relationships(&self, task_id: TaskId) -> Result<Vec<Relationship>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT RELATIONSHIP.TAG, LEFT, RIGHT, OTHER.TITLE FROM RELATIONSHIP
+            JOIN TASK AS OTHER
+              ON OTHER.ID = CASE WHEN LEFT = ?1 THEN RIGHT ELSE LEFT END
+            WHERE LEFT = ?1 OR RIGHT = ?1
+            ORDER BY RELATIONSHIP.TAG, LEFT, RIGHT",
+        )?;
+        let relationships = stmt
+            .query_map((task_id,), |row| {
+                let left: TaskId = row.get(1)?;
+                let right: TaskId = row.get(2)?;
+                Ok(Relationship {
+                    tag: row.get(0)?,
+                    side: if left == task_id {
+                        RelationshipSide::Right(right)
+                    } else {
+                        RelationshipSide::Left(left)
+                    },
+                    title: row.get(3)?,
                 })
-            },
+            })?
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(relationships)
+    }
+
+    pub(super) fn deprioritize(&mut self, task_id: TaskId) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        begin_operation(&tx, "drop")?;
+        deprioritize(&tx, task_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `complete` marks a task complete and takes it off the stack. Completing a repeating task
+    /// creates its next instance at `position` on the stack, which is returned. Undoing the
+    /// completion removes it again.
+    pub(super) fn complete(&mut self, task_id: TaskId, position: usize) -> Result<Option<TaskId>> {
+        let tx = self.conn.transaction()?;
+        check_not_blocked(&tx, task_id)?;
+        begin_operation(&tx, "complete")?;
+        let was_complete = latest_status(&tx, task_id)? == Some(TaskStatus::Complete as i64);
+        update_status(&tx, task_id, TaskStatus::Complete)?;
+        deprioritize(&tx, task_id)?;
+        let next = match recurrence(&tx, task_id)? {
+            Some(rule) if !was_complete => {
+                let next = repeat(&tx, task_id, &rule)?;
+                insert_at(&tx, next, position)?;
+                Some(next)
+            }
+            _ => None,
+        };
+        tx.commit()?;
+        Ok(next)
+    }
+
+    /// `pause` stops the clock on `task_id` by setting it back to todo, leaving it where it is
+    /// on the stack.
+    pub(super) fn pause(&mut self, task_id: TaskId) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        if latest_status(&tx, task_id)? != Some(TaskStatus::InProgress as i64) {
+            return Err(Error::Database(format!("TSK-{task_id} isn't in progress")));
+        }
+        begin_operation(&tx, "pause")?;
+        update_status(&tx, task_id, TaskStatus::Todo)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `resume` starts the clock on `task_id` again.
+    pub(super) fn resume(&mut self, task_id: TaskId) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        if latest_status(&tx, task_id)? == Some(TaskStatus::InProgress as i64) {
+            return Err(Error::Database(format!(
+                "TSK-{task_id} is already in progress"
+            )));
+        }
+        check_not_blocked(&tx, task_id)?;
+        begin_operation(&tx, "resume")?;
+        update_status(&tx, task_id, TaskStatus::InProgress)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `last_paused` is the task that most recently went from in progress back to todo.
+    pub(super) fn last_paused(&self) -> Result<Option<TaskId>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT TASK_ID FROM TASK_STATUS AS PAUSED
+                WHERE STATUS = ?1
+                  AND UPDATED = (SELECT MAX(UPDATED) FROM TASK_STATUS
+                                 WHERE TASK_ID = PAUSED.TASK_ID)
+                  AND (SELECT STATUS FROM TASK_STATUS
+                       WHERE TASK_ID = PAUSED.TASK_ID AND UPDATED < PAUSED.UPDATED
+                       ORDER BY UPDATED DESC LIMIT 1) = ?2
+                ORDER BY UPDATED DESC LIMIT 1",
+                (TaskStatus::Todo as u8, TaskStatus::InProgress as u8),
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// `log_time` records `duration` of work on `task_id` that wasn't tracked, or removes time
+    /// that was when it's negative.
+    pub(super) fn log_time(
+        &mut self,
+        task_id: TaskId,
+        started: DateTime<Utc>,
+        duration: Duration,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        tx.execute(
+            "INSERT INTO TIME_ENTRY(TASK_ID, STARTED, SECONDS) VALUES(?, ?, ?)",
+            (task_id, started.timestamp(), duration.num_seconds()),
         )?;
-        if content.body.is_some() || content.link.is_some() {
-            task.content = Some(content);
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `get_work` returns the time spent on `task_id`, or on every task: each stretch it was in
+    /// progress, the current one counting up to `now`, followed by the manual entries.
+    pub(super) fn get_work(
+        &self,
+        task_id: Option<TaskId>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<WorkInterval>> {
+        let filter = if task_id.is_some() {
+            "WHERE TASK_ID = ?1"
+        } else {
+            "WHERE ?1 IS NULL"
+        };
+        let timestamp = |t: i64| {
+            DateTime::from_timestamp(t, 0)
+                .or(DateTime::from_timestamp(0, 0))
+                .unwrap()
+        };
+        let mut out = Vec::new();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT TASK_ID, STATUS, UPDATED FROM TASK_STATUS {filter} ORDER BY TASK_ID, UPDATED"
+        ))?;
+        let mut rows = stmt.query((task_id,))?;
+        // the task in progress and since when
+        let mut open: Option<(TaskId, DateTime<Utc>)> = None;
+        while let Some(row) = rows.next()? {
+            let id: TaskId = row.get(0)?;
+            let status: u8 = row.get(1)?;
+            let updated = timestamp(row.get(2)?);
+            if let Some((open_id, started)) = open {
+                if open_id != id || status != TaskStatus::InProgress as u8 {
+                    let until = if open_id == id { updated } else { now };
+                    out.push(WorkInterval {
+                        task_id: open_id,
+                        started,
+                        duration: until - started,
+                        manual: false,
+                    });
+                    open = None;
+                }
+            }
+            if open.is_none() && status == TaskStatus::InProgress as u8 {
+                open = Some((id, updated));
+            }
         }
-        Ok(task)
+        if let Some((task_id, started)) = open {
+            out.push(WorkInterval {
+                task_id,
+                started,
+                duration: now - started,
+                manual: false,
+            });
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT TASK_ID, STARTED, SECONDS FROM TIME_ENTRY {filter} ORDER BY STARTED, ID"
+        ))?;
+        let mut rows = stmt.query((task_id,))?;
+        while let Some(row) = rows.next()? {
+            out.push(WorkInterval {
+                task_id: row.get(0)?,
+                started: timestamp(row.get(1)?),
+                duration: Duration::seconds(row.get(2)?),
+                manual: true,
+            });
+        }
+        Ok(out)
     }
 
-    pub(super) fn deprioritize(&self, task_id: TaskId) -> Result<()> {
-        let parent: TaskId =
-            self.conn
-                .query_row("SELECT ID FROM TASK WHERE NEXT = ?", (task_id,), |row| {
-                    row.get(0)
-                })?;
-        self.conn.execute(
-            "UPDATE TASK SET NEXT = (SELECT NEXT FROM TASK WHERE ID = ?) WHERE ID = ?",
-            (task_id, parent),
+    /// `log_focus` records a focus session on `task_id` that ran its full `length`. The time
+    /// itself is already tracked while the task is in progress.
+    pub(super) fn log_focus(
+        &mut self,
+        task_id: TaskId,
+        started: DateTime<Utc>,
+        length: Duration,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        tx.execute(
+            "INSERT INTO FOCUS_SESSION(TASK_ID, STARTED, SECONDS) VALUES(?, ?, ?)",
+            (task_id, started.timestamp(), length.num_seconds()),
         )?;
-        self.conn
-            .execute("UPDATE TASK SET NEXT = NULL WHERE ID = ?", (task_id,))?;
+        tx.commit()?;
         Ok(())
     }
+
+    /// `get_focus_sessions` returns when each focus session on `task_id` started and how long it
+    /// was, oldest first.
+    pub(super) fn get_focus_sessions(
+        &self,
+        task_id: TaskId,
+    ) -> Result<Vec<(DateTime<Utc>, Duration)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT STARTED, SECONDS FROM FOCUS_SESSION WHERE TASK_ID = ? ORDER BY STARTED, ID",
+        )?;
+        let sessions = stmt
+            .query_map((task_id,), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .map(|row| {
+                let (started, seconds) = row?;
+                Ok((
+                    DateTime::from_timestamp(started, 0)
+                        .or(DateTime::from_timestamp(0, 0))
+                        .unwrap(),
+                    Duration::seconds(seconds),
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(sessions)
+    }
+
+    /// `time_spent` is the total time spent on `task_id`, manual entries included.
+    pub(super) fn time_spent(&self, task_id: TaskId, now: DateTime<Utc>) -> Result<Duration> {
+        Ok(self
+            .get_work(Some(task_id), now)?
+            .iter()
+            .fold(Duration::zero(), |total, w| total + w.duration))
+    }
+
     pub(super) fn prioritize(&mut self, task_id: TaskId) -> Result<()> {
         let tx = self.conn.transaction()?;
+        begin_operation(&tx, "prioritize")?;
         set_next_of(&tx, task_id, 0)?;
         tx.commit()?;
         Ok(())
@@ -208,18 +692,118 @@ impl Db {
 
     pub(super) fn set_next_of(&mut self, task_id: TaskId, parent_id: TaskId) -> Result<()> {
         let tx = self.conn.transaction()?;
+        begin_operation(&tx, "reorder")?;
         set_next_of(&tx, task_id, parent_id)?;
         tx.commit()?;
         Ok(())
     }
 
+    /// `insert_at` links `task_id` into the stack so that it ends up at `position`, 0 being the
+    /// top. Positions past the bottom of the stack append to it.
+    pub(super) fn insert_at(&mut self, task_id: TaskId, position: usize) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        begin_operation(&tx, "push")?;
+        insert_at(&tx, task_id, position)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `get_backlog` returns every task that isn't linked into the stack from ROOT.
+    pub(super) fn get_backlog(&self, order: BacklogOrder, descending: bool) -> Result<Vec<Task>> {
+        let direction = if descending { "DESC" } else { "ASC" };
+        let order_by = match order {
+            BacklogOrder::Created => format!("TASK.CREATED {direction}, TASK.ID {direction}"),
+            BacklogOrder::Status => {
+                format!("LATEST.STATUS {direction}, TASK.CREATED DESC, TASK.ID DESC")
+            }
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH RECURSIVE stack(ID) AS (
+                SELECT NEXT FROM TASK WHERE ID = 0
+                UNION
+                SELECT TASK.NEXT FROM TASK JOIN stack ON TASK.ID = stack.ID
+            )
+            SELECT TASK.ID, LATEST.STATUS, TASK.TITLE, TASK.CREATED
+            FROM TASK
+            {LATEST_STATUS_JOIN}
+            WHERE TASK.ID > 0
+              AND TASK.ID NOT IN (SELECT ID FROM stack WHERE ID IS NOT NULL)
+            ORDER BY {order_by}"
+        ))?;
+        let mut rows = stmt.query(())?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            let status_int: u8 = row.get(1)?;
+            out.push(Task::new(
+                row.get(0)?,
+                status_int.try_into()?,
+                row.get(2)?,
+                DateTime::from_timestamp(row.get(3)?, 0)
+                    .or(DateTime::from_timestamp(0, 0))
+                    .unwrap(),
+            ));
+        }
+        Ok(out)
+    }
+
+    /// `undo` reverts the most recent operation that hasn't been undone, returning its name.
+    pub(super) fn undo(&mut self) -> Result<Option<String>> {
+        let tx = self.conn.transaction()?;
+        let operation: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT ID, NAME FROM UNDO_OPERATION WHERE UNDONE = 0 ORDER BY ID DESC LIMIT 1",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((id, name)) = operation else {
+            return Ok(None);
+        };
+        let mut entries = journal_entries(&tx, id)?;
+        entries.reverse();
+        for (seq, entry) in entries {
+            if let Some(snapshot) = entry.undo(&tx)? {
+                tx.execute(
+                    "UPDATE UNDO_ENTRY SET SNAPSHOT = ? WHERE OPERATION_ID = ? AND SEQ = ?",
+                    (snapshot, id, seq),
+                )?;
+            }
+        }
+        tx.execute("UPDATE UNDO_OPERATION SET UNDONE = 1 WHERE ID = ?", (id,))?;
+        tx.commit()?;
+        Ok(Some(name))
+    }
+
+    /// `redo` re-applies the oldest undone operation, returning its name.
+    pub(super) fn redo(&mut self) -> Result<Option<String>> {
+        let tx = self.conn.transaction()?;
+        let operation: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT ID, NAME FROM UNDO_OPERATION WHERE UNDONE = 1 ORDER BY ID LIMIT 1",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((id, name)) = operation else {
+            return Ok(None);
+        };
+        for (_, entry) in journal_entries(&tx, id)? {
+            entry.redo(&tx)?;
+        }
+        tx.execute("UPDATE UNDO_OPERATION SET UNDONE = 0 WHERE ID = ?", (id,))?;
+        tx.commit()?;
+        Ok(Some(name))
+    }
+
     /// `get_top_n_tasks` retrieves the top tasks per the linked-list priority semantics of tasks.
     pub(super) fn get_top_n_tasks(&self, n: u16) -> Result<Vec<Task>> {
         let mut out = Vec::with_capacity(n.into());
         let mut stmt = self.conn.prepare(
-            "SELECT ID, STATUS, TITLE, PRIORITY_TASK.CREATED
+            "SELECT ID, STATUS, TITLE, PRIORITY_TASK.CREATED, DUE, SCHEDULED
                       FROM PRIORITY_TASK
                       JOIN TASK_STATUS ON TASK_STATUS.TASK_ID = priority_task.ID
+                      LEFT JOIN TASK_DATE ON TASK_DATE.TASK_ID = priority_task.ID
                       GROUP BY TASK_STATUS.TASK_ID
                       HAVING MAX(TASK_STATUS.UPDATED)
                       ORDER BY PRIORITY_TASK.ORDERING
@@ -229,68 +813,1768 @@ impl Db {
         while let Some(row) = rows.next()? {
             let status_int: u8 = row.get(1)?;
             let status: TaskStatus = status_int.try_into()?;
-            out.push(Task::new(
+            let mut task = Task::new(
                 row.get(0)?,
                 status,
                 row.get(2)?,
                 DateTime::from_timestamp(row.get(3)?, 0)
                     .or(DateTime::from_timestamp(0, 0))
                     .unwrap(),
-            ));
+            );
+            task.due = row
+                .get::<_, Option<i64>>(4)?
+                .and_then(|t| DateTime::from_timestamp(t, 0));
+            task.scheduled = row
+                .get::<_, Option<i64>>(5)?
+                .and_then(|t| DateTime::from_timestamp(t, 0));
+            out.push(task);
         }
         Ok(out)
     }
 
+    pub(super) fn get_tags(&self, task_id: TaskId) -> Result<Vec<Tag>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT TAG FROM TASKTAG WHERE TASK_ID = ? ORDER BY TAG")?;
+        let tags = stmt
+            .query_map((task_id,), |row| row.get::<_, String>(0))?
+            .map(|tag| tag.map(Tag::from))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(tags)
+    }
+
+    pub(super) fn get_metadata(&self, task_id: TaskId) -> Result<BTreeMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT KEY, VALUE FROM TASK_METADATA WHERE TASK_ID = ?")?;
+        let metadata = stmt
+            .query_map((task_id,), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(metadata)
+    }
+
+    /// `set_metadata` sets `key` to `value` on `task_id`, or removes it when `value` is `None`.
+    pub(super) fn set_metadata(
+        &mut self,
+        task_id: TaskId,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        match value {
+            Some(value) => tx.execute(
+                "INSERT OR REPLACE INTO TASK_METADATA(TASK_ID, KEY, VALUE) VALUES(?, ?, ?)",
+                (task_id, key, value),
+            )?,
+            None => tx.execute(
+                "DELETE FROM TASK_METADATA WHERE TASK_ID = ? AND KEY = ?",
+                (task_id, key),
+            )?,
+        };
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `set_recurrence` makes `task_id` repeat following `rule`, or stops it repeating.
+    pub(super) fn set_recurrence(
+        &mut self,
+        task_id: TaskId,
+        rule: Option<&Recurrence>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        task_exists(&tx, task_id)?;
+        match rule {
+            Some(rule) => tx.execute(
+                "INSERT OR REPLACE INTO TASK_RECURRENCE(TASK_ID, RULE) VALUES(?, ?)",
+                (task_id, rule.to_string()),
+            )?,
+            None => tx.execute("DELETE FROM TASK_RECURRENCE WHERE TASK_ID = ?", (task_id,))?,
+        };
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `set_due` sets the day `task_id` is due, or clears it.
+    pub(super) fn set_due(&mut self, task_id: TaskId, due: Option<DateTime<Utc>>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        set_date(&tx, task_id, "DUE", due)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `set_scheduled` sets the day work on `task_id` is planned to start, or clears it.
+    pub(super) fn set_scheduled(
+        &mut self,
+        task_id: TaskId,
+        scheduled: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        set_date(&tx, task_id, "SCHEDULED", scheduled)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `connect` records that `left` relates to `right` by `tag`, e.g. `tsk-1 blocks tsk-2`,
+    /// creating the tag if needed.
+    pub(super) fn connect(&mut self, left: TaskId, tag: &str, right: TaskId) -> Result<()> {
+        if left == right {
+            return Err(Error::Database(format!(
+                "TSK-{left} can't be connected to itself"
+            )));
+        }
+        let tx = self.conn.transaction()?;
+        for task_id in [left, right] {
+            task_exists(&tx, task_id)?;
+        }
+        if tag == BLOCKS && blocks(&tx, right, left)? {
+            return Err(Error::Database(format!(
+                "TSK-{right} already blocks TSK-{left}, TSK-{left} blocking it would create a cycle"
+            )));
+        }
+        tx.execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (tag,))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO RELATIONSHIP(LEFT, TAG, RIGHT) VALUES(?, ?, ?)",
+            (left, tag, right),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `disconnect` removes a relationship added with `connect`.
+    pub(super) fn disconnect(&mut self, left: TaskId, tag: &str, right: TaskId) -> Result<()> {
+        let removed = self.conn.execute(
+            "DELETE FROM RELATIONSHIP WHERE LEFT = ? AND TAG = ? AND RIGHT = ?",
+            (left, tag, right),
+        )?;
+        if removed == 0 {
+            return Err(Error::Database(format!(
+                "TSK-{left} {tag} TSK-{right} does not exist"
+            )));
+        }
+        Ok(())
+    }
+
+    /// `open_blockers` returns the tasks that block `task_id` and aren't complete or cancelled.
+    pub(super) fn open_blockers(&self, task_id: TaskId) -> Result<Vec<TaskId>> {
+        open_blockers(&self.conn, task_id)
+    }
+
+    /// `is_empty` is true when the database has no tasks, so importing can't lose anything.
+    pub(super) fn is_empty(&self) -> Result<bool> {
+        let count: i64 =
+            self.conn
+                .query_row("SELECT COUNT(*) FROM TASK WHERE ID > 0", (), |row| {
+                    row.get(0)
+                })?;
+        Ok(count == 0)
+    }
+
+    /// `export` copies every task, status change, revision, tag and relationship into a [`Dump`].
+    pub(super) fn export(&self) -> Result<Dump> {
+        // read everything from one snapshot
+        let tx = self.conn.unchecked_transaction()?;
+        let ids = tx
+            .prepare("SELECT ID FROM TASK WHERE ID > 0 ORDER BY ID")?
+            .query_map((), |row| row.get(0))?
+            .collect::<std::result::Result<Vec<TaskId>, _>>()?;
+        let tasks = ids
+            .into_iter()
+            .map(|id| dump_task(&tx, id))
+            .collect::<Result<_>>()?;
+        let all_tags = tx
+            .prepare("SELECT NAME FROM TAG ORDER BY NAME")?
+            .query_map((), |row| row.get(0))?
+            .collect::<std::result::Result<_, _>>()?;
+        let relationships = tx
+            .prepare("SELECT LEFT, TAG, RIGHT FROM RELATIONSHIP ORDER BY LEFT, TAG, RIGHT")?
+            .query_map((), |row| {
+                Ok(DumpRelationship {
+                    left: row.get(0)?,
+                    tag: row.get(1)?,
+                    right: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Dump {
+            version: DUMP_VERSION,
+            stack: stack_of(&tx)?,
+            tags: all_tags,
+            tasks,
+            relationships,
+        })
+    }
+
+    /// `import` writes `dump` into the database and returns the ID each of its tasks ended up
+    /// with. `Replace` backs up and then empties the database first so the IDs are kept,
+    /// `Merge` gives the tasks new IDs and puts its stack below the existing one. Either way the
+    /// undo journal is cleared, since older operations can't be undone across an import.
+    pub(super) fn import(
+        &mut self,
+        dump: &Dump,
+        mode: ImportMode,
+    ) -> Result<HashMap<TaskId, TaskId>> {
+        dump.validate()?;
+        if mode == ImportMode::Replace {
+            backup(&self.conn, MIGRATIONS.len())?;
+        }
+        let tx = self.conn.transaction()?;
+        let ids = import(&tx, dump, mode)?;
+        tx.execute_batch("DELETE FROM UNDO_ENTRY; DELETE FROM UNDO_OPERATION;")?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
     pub(crate) fn make_tag(&self, name: &String) -> Result<()> {
         self.conn
             .execute("INSERT INTO TAG(NAME) VALUES(?)", (name,))?;
         Ok(())
     }
 
-    fn query(&self, query: Vec<QueryArgs>) -> Result<Vec<Task>> {
-        let sql_queries: Vec<String> = query
-            .iter()
-            .map(|query| match query {
+    /// `query` compiles `query` into a single parameterized statement. Every segment must match
+    /// for a task to be returned, i.e. segments are `AND`ed together. When the query contains
+    /// text, results are ranked by bm25 and carry a snippet of the matching text.
+    pub(super) fn query(&self, query: &[QueryArgs]) -> Result<Vec<SearchResult>> {
+        let mut filters = String::new();
+        let mut params: Vec<Value> = Vec::with_capacity(query.len() + 1);
+        let mut terms: Vec<String> = Vec::new();
+        for arg in query {
+            match arg {
                 QueryArgs::Tag(neg, tag) => {
-                    format!("SELECT")
+                    filters.push_str(if *neg { " AND NOT" } else { " AND" });
+                    filters.push_str(
+                        " EXISTS (SELECT 1 FROM TASKTAG
+                        WHERE TASKTAG.TASK_ID = TASK.ID AND TASKTAG.TAG = ?)",
+                    );
+                    params.push(Value::Text(tag.as_str().to_owned()));
                 }
-                QueryArgs::Status(neg, status) => todo!(),
-                QueryArgs::Text(t) => todo!(),
-                QueryArgs::Relation(rel, side) => todo!(),
-            })
-            .collect();
-        todo!();
+                QueryArgs::Status(neg, status) => {
+                    filters.push_str(if *neg {
+                        " AND LATEST.STATUS != ?"
+                    } else {
+                        " AND LATEST.STATUS = ?"
+                    });
+                    params.push(Value::Integer(*status as i64));
+                }
+                QueryArgs::Text(t) => terms.push(fts_phrase(t)),
+                QueryArgs::Relation(rel, side) => {
+                    // The given task sits on `side`, the matching task on the other one.
+                    let task_id = match side {
+                        RelationshipSide::Left(task_id) => {
+                            filters.push_str(
+                                " AND EXISTS (SELECT 1 FROM RELATIONSHIP
+                                WHERE RELATIONSHIP.LEFT = ? AND RELATIONSHIP.TAG = ?
+                                AND RELATIONSHIP.RIGHT = TASK.ID)",
+                            );
+                            task_id
+                        }
+                        RelationshipSide::Right(task_id) => {
+                            filters.push_str(
+                                " AND EXISTS (SELECT 1 FROM RELATIONSHIP
+                                WHERE RELATIONSHIP.RIGHT = ? AND RELATIONSHIP.TAG = ?
+                                AND RELATIONSHIP.LEFT = TASK.ID)",
+                            );
+                            task_id
+                        }
+                    };
+                    params.push(Value::Integer(*task_id as i64));
+                    params.push(Value::Text(rel.clone()));
+                }
+                QueryArgs::Due(neg, filter) => {
+                    filters.push_str(if *neg { " AND NOT" } else { " AND" });
+                    filters.push_str(
+                        " EXISTS (SELECT 1 FROM TASK_DATE
+                        WHERE TASK_DATE.TASK_ID = TASK.ID AND DUE IS NOT NULL",
+                    );
+                    // every filter but `any` matches tasks due before the start of some day
+                    let before = match filter {
+                        DueFilter::Any => None,
+                        DueFilter::Overdue => Some(Day::Today.date()),
+                        DueFilter::By(day) => Some(day.date() + Days::new(1)),
+                    };
+                    if let Some(before) = before {
+                        filters.push_str(" AND DUE < ?");
+                        params.push(Value::Integer(start_of_day(before).timestamp()));
+                    }
+                    filters.push(')');
+                }
+            }
+        }
+        let sql = if terms.is_empty() {
+            format!(
+                "SELECT TASK.ID, LATEST.STATUS, TASK.TITLE, TASK.CREATED, NULL
+                FROM TASK
+                {LATEST_STATUS_JOIN}
+                WHERE TASK.ID > 0{filters}
+                ORDER BY TASK.ID"
+            )
+        } else {
+            params.insert(0, Value::Text(terms.join(" AND ")));
+            format!(
+                "SELECT TASK.ID, LATEST.STATUS, TASK.TITLE, TASK.CREATED,
+                    snippet(TASK_FTS, -1, '{SNIPPET_START}', '{SNIPPET_END}', '…', 12)
+                FROM TASK
+                JOIN TASK_FTS ON TASK_FTS.ROWID = TASK.ID
+                {LATEST_STATUS_JOIN}
+                WHERE TASK_FTS MATCH ? AND TASK.ID > 0{filters}
+                ORDER BY bm25(TASK_FTS, 10.0, 1.0), TASK.ID"
+            )
+        };
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query(params_from_iter(params))?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            let status_int: u8 = row.get(1)?;
+            out.push(SearchResult {
+                task: Task::new(
+                    row.get(0)?,
+                    status_int.try_into()?,
+                    row.get(2)?,
+                    DateTime::from_timestamp(row.get(3)?, 0)
+                        .or(DateTime::from_timestamp(0, 0))
+                        .unwrap(),
+                ),
+                snippet: row.get(4)?,
+            });
+        }
+        Ok(out)
     }
 }
-pub(super) fn update_status(tx: &Transaction, task_id: u64, state: TaskStatus) -> Result<()> {
-    tx.execute(
-        "INSERT INTO TASK_STATUS(TASK_ID, STATUS) VALUES(?, ?)",
-        (task_id, state as u8),
+
+/// The UPDATED of a new TASK_CONTENT row for task `?1`. Revisions are keyed by the second they
+/// were made in, so one made within the same second as the last is pushed a second later.
+const NEXT_REVISION: &str = "MAX(CAST(strftime('%s', 'now') as INT),
+    coalesce((SELECT MAX(UPDATED) + 1 FROM TASK_CONTENT WHERE TASK_ID = ?1), 0))";
+
+const LATEST_STATUS_JOIN: &str = "JOIN (SELECT TASK_ID, STATUS FROM TASK_STATUS
+                  GROUP BY TASK_ID HAVING MAX(UPDATED)) AS LATEST
+              ON LATEST.TASK_ID = TASK.ID";
+
+/// Marks the start of a highlighted match in `SearchResult::snippet`
+pub(crate) const SNIPPET_START: char = '\u{2}';
+/// Marks the end of a highlighted match in `SearchResult::snippet`
+pub(crate) const SNIPPET_END: char = '\u{3}';
+
+/// Quotes `text` as an FTS5 phrase so user input can't inject query syntax. The last token is
+/// matched as a prefix so partially typed words still find results.
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"*", text.replace('"', "\"\""))
+}
+
+/// `migrate` brings the database up to the latest schema version, one transaction per
+/// migration. Existing databases are backed up next to the database file first, and databases
+/// written by a newer tsk are refused.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(Error::Database(format!(
+            "The database is at schema version {version} but this tsk only supports up to version {}, please upgrade tsk.",
+            MIGRATIONS.len()
+        )));
+    }
+    if version == MIGRATIONS.len() {
+        return Ok(());
+    }
+    if version > 0 {
+        backup(conn, version)?;
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        debug!("Migrating database to version {}", i + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn backup(conn: &Connection, version: usize) -> Result<()> {
+    let Some(path) = conn.path().filter(|p| !p.is_empty()) else {
+        return Ok(());
+    };
+    let backup = format!("{path}.v{version}-{}.bak", Utc::now().timestamp());
+    debug!("Backing up database to {backup}");
+    conn.execute("VACUUM INTO ?", (backup,))?;
+    Ok(())
+}
+
+/// How many operations the undo journal keeps.
+const JOURNAL_LIMIT: i64 = 100;
+
+/// `begin_operation` starts a new undoable operation. Every journaled change made in `tx` after
+/// this is undone and redone together. Starting an operation discards anything that could have
+/// been redone.
+fn begin_operation(tx: &Transaction, name: &str) -> Result<()> {
+    tx.execute(
+        "DELETE FROM UNDO_ENTRY WHERE OPERATION_ID IN
+        (SELECT ID FROM UNDO_OPERATION WHERE UNDONE = 1)",
+        (),
+    )?;
+    tx.execute("DELETE FROM UNDO_OPERATION WHERE UNDONE = 1", ())?;
+    tx.execute("INSERT INTO UNDO_OPERATION(NAME) VALUES(?)", (name,))?;
+    let id = tx.last_insert_rowid();
+    tx.execute(
+        "DELETE FROM UNDO_ENTRY WHERE OPERATION_ID <= ?",
+        (id - JOURNAL_LIMIT,),
+    )?;
+    tx.execute(
+        "DELETE FROM UNDO_OPERATION WHERE ID <= ?",
+        (id - JOURNAL_LIMIT,),
     )?;
     Ok(())
 }
-pub(super) fn set_next_of(tx: &Transaction, task_id: TaskId, parent_id: TaskId) -> Result<()> {
-    let parent: Option<TaskId> = tx
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JournalKind {
+    /// `TASK_ID`'s NEXT went from `OLD` to `NEW`
+    Next = 0,
+    /// A TASK_STATUS row with `NEW` at `UPDATED` was added, `OLD` was the status before it
+    Status = 1,
+    /// `TASK_ID` was created with `TITLE` at `UPDATED`. Once undone, `SNAPSHOT` holds the rows
+    /// that were deleted with it
+    Create = 2,
+}
+
+impl TryFrom<u8> for JournalKind {
+    type Error = Error;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Next,
+            1 => Self::Status,
+            2 => Self::Create,
+            _ => {
+                return Err(Error::Bug(format!(
+                    "Invalid journal entry kind {value}, this is a bug."
+                )))
+            }
+        })
+    }
+}
+
+struct JournalEntry {
+    kind: JournalKind,
+    task_id: TaskId,
+    old: Option<i64>,
+    new: Option<i64>,
+    updated: Option<i64>,
+    title: Option<String>,
+    snapshot: Option<String>,
+}
+
+/// Everything kept against a task besides its TASK row, saved when its creation is undone.
+/// Time, tags and the like are added outside the journal, so redo can only restore them from
+/// here.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    task: DumpTask,
+    relationships: Vec<DumpRelationship>,
+}
+
+impl JournalEntry {
+    fn record(&self, tx: &Transaction) -> Result<()> {
+        tx.execute(
+            "INSERT INTO UNDO_ENTRY(OPERATION_ID, SEQ, KIND, TASK_ID, OLD, NEW, UPDATED, TITLE)
+            VALUES(
+                (SELECT MAX(ID) FROM UNDO_OPERATION),
+                (SELECT COUNT(*) FROM UNDO_ENTRY
+                 WHERE OPERATION_ID = (SELECT MAX(ID) FROM UNDO_OPERATION)),
+                ?, ?, ?, ?, ?, ?
+            )",
+            (
+                self.kind as u8,
+                self.task_id,
+                self.old,
+                self.new,
+                self.updated,
+                &self.title,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// `undo` reverts the entry, returning a snapshot to keep for redo.
+    fn undo(&self, tx: &Transaction) -> Result<Option<String>> {
+        match self.kind {
+            JournalKind::Next => {
+                tx.execute(
+                    "UPDATE TASK SET NEXT = ? WHERE ID = ?",
+                    (self.old, self.task_id),
+                )?;
+            }
+            JournalKind::Status => {
+                tx.execute(
+                    "DELETE FROM TASK_STATUS WHERE TASK_ID = ? AND UPDATED = ?",
+                    (self.task_id, self.updated),
+                )?;
+                // A status set within the same second replaces the previous row, put it back
+                if self.old.is_some() && latest_status(tx, self.task_id)? != self.old {
+                    tx.execute(
+                        "INSERT INTO TASK_STATUS(TASK_ID, STATUS, UPDATED) VALUES(?, ?, ?)",
+                        (self.task_id, self.old, self.updated),
+                    )?;
+                }
+            }
+            JournalKind::Create => {
+                let snapshot = Snapshot {
+                    task: dump_task(tx, self.task_id)?,
+                    relationships: tx
+                        .prepare(
+                            "SELECT LEFT, TAG, RIGHT FROM RELATIONSHIP WHERE LEFT = ?1 OR RIGHT = ?1",
+                        )?
+                        .query_map((self.task_id,), |row| {
+                            Ok(DumpRelationship {
+                                left: row.get(0)?,
+                                tag: row.get(1)?,
+                                right: row.get(2)?,
+                            })
+                        })?
+                        .collect::<std::result::Result<_, _>>()?,
+                };
+                // foreign keys are off, so nothing cascades: anything logged against the task
+                // since it was created would otherwise outlive it
+                for table in [
+                    "TASK_STATUS",
+                    "TASK_CONTENT",
+                    "TASK_METADATA",
+                    "TASK_DATE",
+                    "TASK_RECURRENCE",
+                    "TASKTAG",
+                    "TIME_ENTRY",
+                    "FOCUS_SESSION",
+                ] {
+                    tx.execute(
+                        &format!("DELETE FROM {table} WHERE TASK_ID = ?"),
+                        (self.task_id,),
+                    )?;
+                }
+                tx.execute(
+                    "DELETE FROM RELATIONSHIP WHERE LEFT = ?1 OR RIGHT = ?1",
+                    (self.task_id,),
+                )?;
+                tx.execute("DELETE FROM TASK WHERE ID = ?", (self.task_id,))?;
+                return serde_json::to_string(&snapshot).map(Some).map_err(|e| {
+                    Error::Internal(format!("Unable to save TSK-{} for redo: {e}", self.task_id))
+                });
+            }
+        }
+        Ok(None)
+    }
+
+    fn redo(&self, tx: &Transaction) -> Result<()> {
+        match self.kind {
+            JournalKind::Next => {
+                tx.execute(
+                    "UPDATE TASK SET NEXT = ? WHERE ID = ?",
+                    (self.new, self.task_id),
+                )?;
+            }
+            JournalKind::Status => {
+                tx.execute(
+                    "INSERT INTO TASK_STATUS(TASK_ID, STATUS, UPDATED) VALUES(?, ?, ?)",
+                    (self.task_id, self.new, self.updated),
+                )?;
+            }
+            JournalKind::Create => {
+                tx.execute(
+                    "INSERT INTO TASK(ID, TITLE, CREATED) VALUES(?, ?, ?)",
+                    (self.task_id, &self.title, self.updated),
+                )?;
+                let Some(snapshot) = &self.snapshot else {
+                    return Ok(());
+                };
+                let snapshot: Snapshot = serde_json::from_str(snapshot).map_err(|e| {
+                    Error::Bug(format!(
+                        "Unable to read what undo saved of TSK-{}, this is a bug: {e}",
+                        self.task_id
+                    ))
+                })?;
+                insert_task_rows(tx, self.task_id, &snapshot.task)?;
+                for r in &snapshot.relationships {
+                    tx.execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (&r.tag,))?;
+                    tx.execute(
+                        "INSERT OR IGNORE INTO RELATIONSHIP(LEFT, TAG, RIGHT) VALUES(?, ?, ?)",
+                        (r.left, &r.tag, r.right),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `journal_entries` reads the entries of `operation_id` in the order they were made, each with
+/// its sequence number.
+fn journal_entries(tx: &Transaction, operation_id: i64) -> Result<Vec<(i64, JournalEntry)>> {
+    let mut stmt = tx.prepare(
+        "SELECT KIND, TASK_ID, OLD, NEW, UPDATED, TITLE, SNAPSHOT, SEQ FROM UNDO_ENTRY
+        WHERE OPERATION_ID = ? ORDER BY SEQ",
+    )?;
+    let mut rows = stmt.query((operation_id,))?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let kind: u8 = row.get(0)?;
+        out.push((
+            row.get(7)?,
+            JournalEntry {
+                kind: kind.try_into()?,
+                task_id: row.get(1)?,
+                old: row.get(2)?,
+                new: row.get(3)?,
+                updated: row.get(4)?,
+                title: row.get(5)?,
+                snapshot: row.get(6)?,
+            },
+        ));
+    }
+    Ok(out)
+}
+
+/// The relationship tag that orders work: `tsk-1 blocks tsk-2` means TSK-2 can't be started or
+/// completed until TSK-1 is complete or cancelled.
+pub(crate) const BLOCKS: &str = "blocks";
+
+fn open_blockers(conn: &Connection, task_id: TaskId) -> Result<Vec<TaskId>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT TASK.ID FROM RELATIONSHIP
+        JOIN TASK ON TASK.ID = RELATIONSHIP.LEFT
+        {LATEST_STATUS_JOIN}
+        WHERE RELATIONSHIP.RIGHT = ? AND RELATIONSHIP.TAG = ? AND LATEST.STATUS NOT IN (?, ?)
+        ORDER BY TASK.ID"
+    ))?;
+    let blockers = stmt
+        .query_map(
+            (
+                task_id,
+                BLOCKS,
+                TaskStatus::Complete as u8,
+                TaskStatus::Cancelled as u8,
+            ),
+            |row| row.get(0),
+        )?
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(blockers)
+}
+
+fn check_not_blocked(tx: &Transaction, task_id: TaskId) -> Result<()> {
+    let blockers = open_blockers(tx, task_id)?;
+    if blockers.is_empty() {
+        return Ok(());
+    }
+    Err(Error::Blocked(format!(
+        "TSK-{task_id} is blocked by {}",
+        blockers
+            .iter()
+            .map(|id| format!("TSK-{id}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )))
+}
+
+/// Whether `left` blocks `right`, directly or through other tasks.
+fn blocks(tx: &Transaction, left: TaskId, right: TaskId) -> Result<bool> {
+    Ok(tx.query_row(
+        "WITH RECURSIVE blocked(ID) AS (
+            SELECT ?1
+            UNION
+            SELECT RELATIONSHIP.RIGHT FROM RELATIONSHIP
+            JOIN blocked ON RELATIONSHIP.LEFT = blocked.ID
+            WHERE RELATIONSHIP.TAG = ?3
+        )
+        SELECT EXISTS(SELECT 1 FROM blocked WHERE ID = ?2)",
+        (left, right, BLOCKS),
+        |row| row.get(0),
+    )?)
+}
+
+fn task_exists(tx: &Transaction, task_id: TaskId) -> Result<()> {
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM TASK WHERE ID = ? AND ID > 0)",
+        (task_id,),
+        |row| row.get(0),
+    )?;
+    if exists {
+        Ok(())
+    } else {
+        Err(Error::Database(format!("TSK-{task_id} does not exist")))
+    }
+}
+
+fn latest_status(tx: &Transaction, task_id: TaskId) -> Result<Option<i64>> {
+    Ok(tx
+        .query_row(
+            "SELECT STATUS FROM TASK_STATUS
+            WHERE TASK_ID = ?
+            GROUP BY TASK_ID HAVING MAX(UPDATED)",
+            (task_id,),
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn create_task(tx: &Transaction, title: String) -> Result<TaskId> {
+    let created = Utc::now().timestamp();
+    tx.execute(
+        "INSERT INTO TASK(TITLE, CREATED) VALUES(?, ?)",
+        (&title, created),
+    )?;
+    let row_id = tx.last_insert_rowid();
+    let task_id = tx.query_row("SELECT ID FROM TASK WHERE ROWID = ?", (row_id,), |row| {
+        row.get(0)
+    })?;
+    JournalEntry {
+        kind: JournalKind::Create,
+        task_id,
+        old: None,
+        new: None,
+        updated: Some(created),
+        title: Some(title),
+        snapshot: None,
+    }
+    .record(tx)?;
+    update_status(tx, task_id, TaskStatus::Todo)?;
+    Ok(task_id)
+}
+
+pub(super) fn update_status(tx: &Transaction, task_id: u64, state: TaskStatus) -> Result<()> {
+    let old = latest_status(tx, task_id)?;
+    let updated = Utc::now().timestamp();
+    tx.execute(
+        "INSERT INTO TASK_STATUS(TASK_ID, STATUS, UPDATED) VALUES(?, ?, ?)",
+        (task_id, state as u8, updated),
+    )?;
+    JournalEntry {
+        kind: JournalKind::Status,
+        task_id,
+        old,
+        new: Some(state as i64),
+        updated: Some(updated),
+        title: None,
+        snapshot: None,
+    }
+    .record(tx)?;
+    Ok(())
+}
+
+fn next_of(tx: &Transaction, task_id: TaskId) -> Result<Option<TaskId>> {
+    Ok(tx
+        .query_row("SELECT NEXT FROM TASK WHERE ID = ?", (task_id,), |row| {
+            row.get(0)
+        })
+        .optional()?
+        .flatten())
+}
+
+/// `set_next` points `task_id` at `next`, journaling the pointer it replaced.
+fn set_next(tx: &Transaction, task_id: TaskId, next: Option<TaskId>) -> Result<()> {
+    let old = next_of(tx, task_id)?;
+    if old == next {
+        return Ok(());
+    }
+    tx.execute("UPDATE TASK SET NEXT = ? WHERE ID = ?", (next, task_id))?;
+    JournalEntry {
+        kind: JournalKind::Next,
+        task_id,
+        old: old.map(|id| id as i64),
+        new: next.map(|id| id as i64),
+        updated: None,
+        title: None,
+        snapshot: None,
+    }
+    .record(tx)?;
+    Ok(())
+}
+
+fn parent_of(tx: &Transaction, task_id: TaskId) -> Result<Option<TaskId>> {
+    Ok(tx
         .query_row("SELECT ID FROM TASK WHERE NEXT = ?", (task_id,), |row| {
             row.get(0)
         })
-        .optional()?;
+        .optional()?)
+}
+
+fn deprioritize(tx: &Transaction, task_id: TaskId) -> Result<()> {
+    if let Some(parent) = parent_of(tx, task_id)? {
+        set_next(tx, parent, next_of(tx, task_id)?)?;
+    }
+    set_next(tx, task_id, None)?;
+    Ok(())
+}
+
+pub(super) fn set_next_of(tx: &Transaction, task_id: TaskId, parent_id: TaskId) -> Result<()> {
     // Remove the task from continuum
-    if let Some(parent) = parent {
-        tx.execute(
-            "UPDATE TASK SET NEXT = (SELECT NEXT FROM TASK WHERE ID = ?) WHERE ID = ?",
-            (task_id, parent),
-        )?;
+    if let Some(parent) = parent_of(tx, task_id)? {
+        set_next(tx, parent, next_of(tx, task_id)?)?;
     }
     // Set the parents previous next to the the current tasks next so we don't lose it
+    set_next(tx, task_id, next_of(tx, parent_id)?)?;
+    // Set the task as the next from the parent
+    set_next(tx, parent_id, Some(task_id))?;
+    Ok(())
+}
+
+/// `recurrence` reads the rule `task_id` repeats by, if any.
+fn recurrence(conn: &Connection, task_id: TaskId) -> Result<Option<Recurrence>> {
+    let rule: Option<String> = conn
+        .query_row(
+            "SELECT RULE FROM TASK_RECURRENCE WHERE TASK_ID = ?",
+            (task_id,),
+            |row| row.get(0),
+        )
+        .optional()?;
+    rule.map(|rule| rule.parse()).transpose()
+}
+
+/// `repeat` creates the next instance of `task_id`, with the same title, content, tags and
+/// rule, due on the next day `rule` gives. The new task isn't on the stack yet.
+fn repeat(tx: &Transaction, task_id: TaskId, rule: &Recurrence) -> Result<TaskId> {
+    let (due, _) = dates(tx, task_id)?;
+    let due = rule.next(due.map(local_date), Local::now().date_naive())?;
+    let title: String = tx.query_row("SELECT TITLE FROM TASK WHERE ID = ?", (task_id,), |row| {
+        row.get(0)
+    })?;
+    let next = create_task(tx, title)?;
     tx.execute(
-        "UPDATE TASK SET NEXT = (SELECT NEXT FROM TASK WHERE ID = ?) WHERE ID = ?",
-        (parent_id, task_id),
+        &format!(
+            "INSERT INTO TASK_CONTENT(TASK_ID, BODY, LINK, UPDATED)
+            SELECT ?1, BODY, LINK, {NEXT_REVISION}
+            FROM TASK_CONTENT WHERE TASK_ID = ?2
+            ORDER BY UPDATED DESC LIMIT 1"
+        ),
+        (next, task_id),
+    )?;
+    tx.execute(
+        "INSERT INTO TASKTAG(TAG, TASK_ID) SELECT TAG, ? FROM TASKTAG WHERE TASK_ID = ?",
+        (next, task_id),
+    )?;
+    tx.execute(
+        "INSERT INTO TASK_RECURRENCE(TASK_ID, RULE) VALUES(?, ?)",
+        (next, rule.to_string()),
+    )?;
+    set_date(tx, next, "DUE", Some(start_of_day(due)))?;
+    Ok(next)
+}
+
+/// `insert_at` links `task_id` into the stack at `position`, see [`Db::insert_at`].
+fn insert_at(tx: &Transaction, task_id: TaskId, position: usize) -> Result<()> {
+    let mut parent = 0;
+    for _ in 0..position {
+        match next_of(tx, parent)? {
+            Some(next) if next != task_id => parent = next,
+            _ => break,
+        }
+    }
+    set_next_of(tx, task_id, parent)
+}
+
+/// A task's due and scheduled dates.
+type Dates = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// `dates` reads the due and scheduled dates of `task_id`.
+fn dates(conn: &Connection, task_id: TaskId) -> Result<Dates> {
+    let dates: Option<(Option<i64>, Option<i64>)> = conn
+        .query_row(
+            "SELECT DUE, SCHEDULED FROM TASK_DATE WHERE TASK_ID = ?",
+            (task_id,),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (due, scheduled) = dates.unwrap_or_default();
+    let timestamp = |t: Option<i64>| t.and_then(|t| DateTime::from_timestamp(t, 0));
+    Ok((timestamp(due), timestamp(scheduled)))
+}
+
+/// `set_date` sets `column` of TASK_DATE, `DUE` or `SCHEDULED`, for `task_id`.
+fn set_date(
+    tx: &Transaction,
+    task_id: TaskId,
+    column: &str,
+    value: Option<DateTime<Utc>>,
+) -> Result<()> {
+    task_exists(tx, task_id)?;
+    tx.execute(
+        "INSERT OR IGNORE INTO TASK_DATE(TASK_ID) VALUES(?)",
+        (task_id,),
     )?;
-    // Set the task as the next from the parent
     tx.execute(
-        "UPDATE TASK SET NEXT = ? WHERE ID = ?",
-        (task_id, parent_id),
+        &format!("UPDATE TASK_DATE SET {column} = ? WHERE TASK_ID = ?"),
+        (value.map(|v| v.timestamp()), task_id),
     )?;
     Ok(())
 }
+
+/// `stack_of` walks the NEXT chain from ROOT. Unlike the `priority_task` view it isn't limited
+/// to the top of the stack.
+fn stack_of(tx: &Transaction) -> Result<Vec<TaskId>> {
+    let mut stack = Vec::new();
+    let mut next = next_of(tx, 0)?;
+    while let Some(task_id) = next {
+        if stack.contains(&task_id) {
+            return Err(Error::Bug(format!("The stack loops back to TSK-{task_id}")));
+        }
+        stack.push(task_id);
+        next = next_of(tx, task_id)?;
+    }
+    Ok(stack)
+}
+
+/// `dump_task` reads `task_id` and everything kept against it, except its relationships.
+fn dump_task(conn: &Connection, task_id: TaskId) -> Result<DumpTask> {
+    let timestamp = |seconds: i64| {
+        DateTime::from_timestamp(seconds, 0)
+            .or(DateTime::from_timestamp(0, 0))
+            .unwrap()
+    };
+    let (title, created): (String, i64) = conn.query_row(
+        "SELECT TITLE, CREATED FROM TASK WHERE ID = ?",
+        (task_id,),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let (due, scheduled) = dates(conn, task_id)?;
+    Ok(DumpTask {
+        id: task_id,
+        title,
+        created: timestamp(created),
+        statuses: conn
+            .prepare_cached(
+                "SELECT STATUS, UPDATED FROM TASK_STATUS WHERE TASK_ID = ? ORDER BY UPDATED",
+            )?
+            .query_map((task_id,), |row| Ok((row.get::<_, u8>(0)?, row.get(1)?)))?
+            .map(|row| {
+                let (status, updated) = row?;
+                Ok(DumpStatus {
+                    status: status.try_into()?,
+                    updated: timestamp(updated),
+                })
+            })
+            .collect::<Result<_>>()?,
+        revisions: conn
+            .prepare_cached(
+                "SELECT UPDATED, BODY, LINK FROM TASK_CONTENT WHERE TASK_ID = ? ORDER BY UPDATED",
+            )?
+            .query_map((task_id,), |row| {
+                Ok(DumpRevision {
+                    updated: timestamp(row.get(0)?),
+                    body: row.get(1)?,
+                    link: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?,
+        tags: conn
+            .prepare_cached("SELECT TAG FROM TASKTAG WHERE TASK_ID = ? ORDER BY TAG")?
+            .query_map((task_id,), |row| row.get(0))?
+            .collect::<std::result::Result<_, _>>()?,
+        metadata: conn
+            .prepare_cached("SELECT KEY, VALUE FROM TASK_METADATA WHERE TASK_ID = ?")?
+            .query_map((task_id,), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<_, _>>()?,
+        due,
+        scheduled,
+        recurrence: recurrence(conn, task_id)?,
+        time_entries: conn
+            .prepare_cached(
+                "SELECT STARTED, SECONDS FROM TIME_ENTRY WHERE TASK_ID = ? ORDER BY STARTED, ID",
+            )?
+            .query_map((task_id,), |row| {
+                Ok(DumpTimeEntry {
+                    started: timestamp(row.get(0)?),
+                    seconds: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?,
+        focus_sessions: conn
+            .prepare_cached(
+                "SELECT STARTED, SECONDS FROM FOCUS_SESSION WHERE TASK_ID = ? ORDER BY STARTED, ID",
+            )?
+            .query_map((task_id,), |row| {
+                Ok(DumpTimeEntry {
+                    started: timestamp(row.get(0)?),
+                    seconds: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<_, _>>()?,
+    })
+}
+
+/// `insert_task_rows` writes everything `task` keeps against it, except its relationships, for
+/// the already inserted `task_id`.
+fn insert_task_rows(tx: &Transaction, task_id: TaskId, task: &DumpTask) -> Result<()> {
+    for status in &task.statuses {
+        tx.execute(
+            "INSERT INTO TASK_STATUS(STATUS, UPDATED, TASK_ID) VALUES(?, ?, ?)",
+            (status.status as u8, status.updated.timestamp(), task_id),
+        )?;
+    }
+    // oldest first, so the last insert leaves the current content in the search index
+    let mut revisions = task.revisions.iter().collect::<Vec<_>>();
+    revisions.sort_by_key(|r| r.updated);
+    for revision in revisions {
+        tx.execute(
+            "INSERT OR REPLACE INTO TASK_CONTENT(TASK_ID, BODY, LINK, UPDATED)
+            VALUES(?, ?, ?, ?)",
+            (
+                task_id,
+                &revision.body,
+                &revision.link,
+                revision.updated.timestamp(),
+            ),
+        )?;
+    }
+    for tag in &task.tags {
+        tx.execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (tag,))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO TASKTAG(TAG, TASK_ID) VALUES(?, ?)",
+            (tag, task_id),
+        )?;
+    }
+    for (key, value) in &task.metadata {
+        tx.execute(
+            "INSERT OR REPLACE INTO TASK_METADATA(TASK_ID, KEY, VALUE) VALUES(?, ?, ?)",
+            (task_id, key, value),
+        )?;
+    }
+    if task.due.is_some() || task.scheduled.is_some() {
+        tx.execute(
+            "INSERT INTO TASK_DATE(TASK_ID, DUE, SCHEDULED) VALUES(?, ?, ?)",
+            (
+                task_id,
+                task.due.map(|t| t.timestamp()),
+                task.scheduled.map(|t| t.timestamp()),
+            ),
+        )?;
+    }
+    if let Some(rule) = &task.recurrence {
+        tx.execute(
+            "INSERT INTO TASK_RECURRENCE(TASK_ID, RULE) VALUES(?, ?)",
+            (task_id, rule.to_string()),
+        )?;
+    }
+    for entry in &task.time_entries {
+        tx.execute(
+            "INSERT INTO TIME_ENTRY(TASK_ID, STARTED, SECONDS) VALUES(?, ?, ?)",
+            (task_id, entry.started.timestamp(), entry.seconds),
+        )?;
+    }
+    for session in &task.focus_sessions {
+        tx.execute(
+            "INSERT INTO FOCUS_SESSION(TASK_ID, STARTED, SECONDS) VALUES(?, ?, ?)",
+            (task_id, session.started.timestamp(), session.seconds),
+        )?;
+    }
+    Ok(())
+}
+
+fn import(tx: &Transaction, dump: &Dump, mode: ImportMode) -> Result<HashMap<TaskId, TaskId>> {
+    let mut bottom = 0;
+    match mode {
+        ImportMode::Replace => tx.execute_batch(
+            "DELETE FROM RELATIONSHIP;
+            DELETE FROM TASK_DATE;
+            DELETE FROM TASK_RECURRENCE;
+            DELETE FROM TIME_ENTRY;
+            DELETE FROM FOCUS_SESSION;
+            DELETE FROM TASK_METADATA;
+            DELETE FROM TASKTAG;
+            DELETE FROM TAG;
+            DELETE FROM TASK_CONTENT;
+            DELETE FROM TASK_STATUS;
+            DELETE FROM TASK WHERE ID > 0;
+            UPDATE TASK SET NEXT = NULL WHERE ID = 0;",
+        )?,
+        ImportMode::Merge => {
+            if let Some(last) = stack_of(tx)?.last() {
+                bottom = *last;
+            }
+        }
+    }
+    for tag in &dump.tags {
+        tx.execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (tag,))?;
+    }
+    let mut ids = HashMap::with_capacity(dump.tasks.len());
+    for task in &dump.tasks {
+        let created = task.created.timestamp();
+        match mode {
+            ImportMode::Replace => tx.execute(
+                "INSERT INTO TASK(ID, TITLE, CREATED) VALUES(?, ?, ?)",
+                (task.id, &task.title, created),
+            )?,
+            ImportMode::Merge => tx.execute(
+                "INSERT INTO TASK(TITLE, CREATED) VALUES(?, ?)",
+                (&task.title, created),
+            )?,
+        };
+        let task_id: TaskId = tx.query_row(
+            "SELECT ID FROM TASK WHERE ROWID = ?",
+            (tx.last_insert_rowid(),),
+            |row| row.get(0),
+        )?;
+        ids.insert(task.id, task_id);
+        // a task without any status would be missing from every view
+        if task.statuses.is_empty() {
+            tx.execute(
+                "INSERT INTO TASK_STATUS(STATUS, UPDATED, TASK_ID) VALUES(?, ?, ?)",
+                (TaskStatus::Todo as u8, created, task_id),
+            )?;
+        }
+        insert_task_rows(tx, task_id, task)?;
+    }
+    for r in &dump.relationships {
+        tx.execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (&r.tag,))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO RELATIONSHIP(LEFT, TAG, RIGHT) VALUES(?, ?, ?)",
+            (ids[&r.left], &r.tag, ids[&r.right]),
+        )?;
+    }
+    for task_id in &dump.stack {
+        let task_id = ids[task_id];
+        tx.execute("UPDATE TASK SET NEXT = ? WHERE ID = ?", (task_id, bottom))?;
+        bottom = task_id;
+    }
+    if mode == ImportMode::Replace {
+        tx.execute(
+            "UPDATE sqlite_sequence SET SEQ = (SELECT MAX(ID) FROM TASK) WHERE NAME = 'TASK'",
+            (),
+        )?;
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tag(db: &Db, task_id: TaskId, name: &str) {
+        db.conn
+            .execute("INSERT OR IGNORE INTO TAG(NAME) VALUES(?)", (name,))
+            .unwrap();
+        db.conn
+            .execute(
+                "INSERT INTO TASKTAG(TAG, TASK_ID) VALUES(?, ?)",
+                (name, task_id),
+            )
+            .unwrap();
+    }
+
+    fn set_status(db: &Db, task_id: TaskId, status: TaskStatus, updated: i64) {
+        db.conn
+            .execute(
+                "INSERT INTO TASK_STATUS(TASK_ID, STATUS, UPDATED) VALUES(?, ?, ?)",
+                (task_id, status as u8, updated),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_task() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("write the report".to_string()).unwrap();
+        tag(&db, a, "work");
+        set_status(&db, a, TaskStatus::InProgress, i64::MAX - 1);
+
+        let task = db.get_task(a).unwrap();
+        assert_eq!(task.title, "write the report");
+        assert_eq!(task.status, TaskStatus::InProgress);
+        assert!(task.content.is_none());
+        assert_eq!(task.tags, vec![Tag::from("work".to_string())]);
+        assert!(db.get_task(1000).is_err());
+    }
+
+    #[test]
+    fn test_status_history() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("a".to_string()).unwrap();
+        db.conn
+            .execute(
+                "UPDATE TASK_STATUS SET UPDATED = 100 WHERE TASK_ID = ?",
+                (a,),
+            )
+            .unwrap();
+        set_status(&db, a, TaskStatus::InProgress, 200);
+        set_status(&db, a, TaskStatus::InProgress, 300);
+        set_status(&db, a, TaskStatus::Complete, 8300);
+
+        let history = db.get_status_history(a).unwrap();
+        assert_eq!(
+            history.iter().map(|c| c.status).collect::<Vec<_>>(),
+            vec![
+                TaskStatus::Todo,
+                TaskStatus::InProgress,
+                TaskStatus::Complete
+            ]
+        );
+        assert_eq!(history[0].duration(Utc::now()).num_seconds(), 100);
+        assert_eq!(history[1].duration(Utc::now()).num_seconds(), 8100);
+        assert_eq!(history[2].until, None);
+    }
+
+    #[test]
+    fn test_revisions() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("a".to_string()).unwrap();
+        db.update_content(a, Some("first".to_string()), None)
+            .unwrap();
+        db.update_content(
+            a,
+            Some("second".to_string()),
+            Some("https://example.com".to_string()),
+        )
+        .unwrap();
+        let revisions = db.get_revisions(a).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert!(revisions[0].updated < revisions[1].updated);
+        assert!(revisions[1].content.link.is_some());
+
+        db.restore_revision(a, revisions[0].updated).unwrap();
+        let revisions = db.get_revisions(a).unwrap();
+        assert_eq!(revisions.len(), 3);
+        let content = db.get_task(a).unwrap().content.unwrap();
+        assert_eq!(content.body.as_deref(), Some("first"));
+        assert!(content.link.is_none());
+        assert!(db.restore_revision(a, DateTime::UNIX_EPOCH).is_err());
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("a".to_string()).unwrap();
+        let b = db.create_task("b".to_string()).unwrap();
+        db.connect(a, "blocks", b).unwrap();
+        assert!(db.connect(a, "blocks", a).is_err());
+        assert!(db.connect(a, "blocks", 1000).is_err());
+
+        assert_eq!(
+            db.get_task(a).unwrap().relationships,
+            vec![Relationship {
+                tag: "blocks".to_string(),
+                side: RelationshipSide::Right(b),
+                title: "b".to_string(),
+            }]
+        );
+        assert_eq!(
+            db.get_task(b).unwrap().relationships,
+            vec![Relationship {
+                tag: "blocks".to_string(),
+                side: RelationshipSide::Left(a),
+                title: "a".to_string(),
+            }]
+        );
+        assert_eq!(
+            ids(db
+                .query(&[QueryArgs::Relation(
+                    "blocks".to_string(),
+                    RelationshipSide::Right(b)
+                )])
+                .unwrap()),
+            vec![a]
+        );
+
+        db.disconnect(a, "blocks", b).unwrap();
+        assert!(db.get_task(a).unwrap().relationships.is_empty());
+        assert!(db.disconnect(a, "blocks", b).is_err());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        let b = db.push_task("b".to_string()).unwrap();
+        let c = db.push_task("c".to_string()).unwrap();
+        let d = db.create_task("off the stack".to_string()).unwrap();
+        set_status(&db, a, TaskStatus::InProgress, 1);
+        set_status(&db, a, TaskStatus::Todo, 2);
+        db.update_content(b, Some("first".to_string()), None)
+            .unwrap();
+        db.update_content(b, Some("second".to_string()), Some("not a uri".to_string()))
+            .unwrap();
+        tag(&db, c, "work");
+        db.set_metadata(d, "due", Some("2023-12-01")).unwrap();
+        db.log_time(d, Utc::now(), Duration::minutes(45)).unwrap();
+        db.make_tag(&"unused".to_string()).unwrap();
+        db.connect(a, BLOCKS, c).unwrap();
+        db.prioritize(a).unwrap();
+        let dump = db.export().unwrap();
+        assert_eq!(dump.stack, vec![a, c, b]);
+        assert_eq!(dump.tasks.len(), 4);
+        assert_eq!(dump.tasks[0].statuses.len(), 3);
+        assert_eq!(dump.tasks[1].revisions.len(), 2);
+
+        let mut copy = Db::in_memory().unwrap();
+        let mapping = copy.import(&dump, ImportMode::Replace).unwrap();
+        assert!(mapping.iter().all(|(old, new)| old == new));
+        assert_eq!(copy.export().unwrap(), dump);
+        assert_eq!(copy.undo().unwrap(), None);
+        assert_eq!(
+            ids(copy
+                .query(&[QueryArgs::Text("second".to_string())])
+                .unwrap()),
+            vec![b]
+        );
+        // new tasks continue after the imported IDs
+        assert_eq!(copy.create_task("e".to_string()).unwrap(), d + 1);
+
+        let mut merged = Db::in_memory().unwrap();
+        let existing = merged.push_task("existing".to_string()).unwrap();
+        let mapping = merged.import(&dump, ImportMode::Merge).unwrap();
+        assert_eq!(mapping[&a], 2);
+        assert_eq!(
+            stack(&merged),
+            vec![existing, mapping[&a], mapping[&c], mapping[&b]]
+        );
+        assert_eq!(
+            merged.open_blockers(mapping[&c]).unwrap(),
+            vec![mapping[&a]]
+        );
+        assert_eq!(
+            merged.get_task(mapping[&c]).unwrap().tags,
+            vec![Tag::from("work".to_string())]
+        );
+        assert_eq!(merged.get_revisions(mapping[&b]).unwrap().len(), 2);
+        assert_eq!(
+            merged.get_metadata(mapping[&d]).unwrap()["due"],
+            "2023-12-01"
+        );
+        assert_eq!(
+            merged.time_spent(mapping[&d], Utc::now()).unwrap(),
+            Duration::minutes(45)
+        );
+        assert_eq!(merged.export().unwrap().tasks.len(), 5);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        db.set_metadata(a, "due", Some("2023-12-01")).unwrap();
+        db.set_metadata(a, "due", Some("2023-12-02")).unwrap();
+        db.set_metadata(a, "rec", Some("1w")).unwrap();
+        db.set_metadata(a, "rec", None).unwrap();
+        assert_eq!(
+            db.get_task(a).unwrap().metadata,
+            BTreeMap::from([("due".to_string(), "2023-12-02".to_string())])
+        );
+        assert!(db.set_metadata(a + 1, "due", Some("today")).is_err());
+    }
+
+    #[test]
+    fn test_dates() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        let b = db.push_task("b".to_string()).unwrap();
+        let c = db.push_task("c".to_string()).unwrap();
+        let day = |offset: i64| {
+            Some(start_of_day(
+                Day::Today.date() + chrono::Duration::days(offset),
+            ))
+        };
+        db.set_due(a, day(-1)).unwrap();
+        db.set_due(b, day(2)).unwrap();
+        db.set_scheduled(b, day(1)).unwrap();
+        db.set_scheduled(c, day(0)).unwrap();
+        db.set_scheduled(c, None).unwrap();
+        let task = db.get_task(b).unwrap();
+        assert_eq!((task.due, task.scheduled), (day(2), day(1)));
+        let task = db.get_task(c).unwrap();
+        assert_eq!((task.due, task.scheduled), (None, None));
+        let top = db.get_top_n_tasks(3).unwrap();
+        assert_eq!(top[1].due, day(2));
+        assert_eq!(top[1].scheduled, day(1));
+        assert!(db.set_due(c + 1, day(0)).is_err());
+
+        let query = |args: &[QueryArgs]| -> Vec<TaskId> {
+            let mut ids: Vec<TaskId> = db.query(args).unwrap().iter().map(|r| r.task.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(query(&[QueryArgs::Due(false, DueFilter::Any)]), vec![a, b]);
+        assert_eq!(query(&[QueryArgs::Due(true, DueFilter::Any)]), vec![c]);
+        assert_eq!(query(&[QueryArgs::Due(false, DueFilter::Overdue)]), vec![a]);
+        assert_eq!(
+            query(&[QueryArgs::Due(false, DueFilter::By(Day::Tomorrow))]),
+            vec![a]
+        );
+        let in_two_days = Day::On(Day::Today.date() + chrono::Duration::days(2));
+        assert_eq!(
+            query(&[QueryArgs::Due(false, DueFilter::By(in_two_days))]),
+            vec![a, b]
+        );
+    }
+
+    #[test]
+    fn test_backlog() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        let b = db.push_task("b".to_string()).unwrap();
+        let c = db.create_task("c".to_string()).unwrap();
+        let d = db.create_task("d".to_string()).unwrap();
+        db.conn.execute("UPDATE TASK SET CREATED = ID", ()).unwrap();
+        db.update_status(d, TaskStatus::Cancelled).unwrap();
+        assert_eq!(stack(&db), vec![b, a]);
+
+        let backlog = |db: &Db, order, descending| -> Vec<TaskId> {
+            db.get_backlog(order, descending)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(backlog(&db, BacklogOrder::Created, false), vec![c, d]);
+        assert_eq!(backlog(&db, BacklogOrder::Created, true), vec![d, c]);
+        assert_eq!(backlog(&db, BacklogOrder::Status, true), vec![d, c]);
+
+        db.insert_at(c, 1).unwrap();
+        assert_eq!(stack(&db), vec![b, c, a]);
+        db.insert_at(d, 10).unwrap();
+        assert_eq!(stack(&db), vec![b, c, a, d]);
+        db.deprioritize(c).unwrap();
+        assert_eq!(backlog(&db, BacklogOrder::Created, false), vec![c]);
+        db.insert_at(c, 0).unwrap();
+        assert_eq!(stack(&db), vec![c, b, a, d]);
+        assert!(db
+            .get_backlog(BacklogOrder::Created, false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_blocking() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("a".to_string()).unwrap();
+        let b = db.create_task("b".to_string()).unwrap();
+        let c = db.create_task("c".to_string()).unwrap();
+        db.connect(a, BLOCKS, b).unwrap();
+        db.connect(b, BLOCKS, c).unwrap();
+        assert!(db.connect(c, BLOCKS, a).is_err());
+        // other relationships may form cycles
+        db.connect(c, "relates", a).unwrap();
+
+        assert_eq!(db.open_blockers(b).unwrap(), vec![a]);
+        assert!(matches!(
+            db.update_status(b, TaskStatus::InProgress),
+            Err(Error::Blocked(_))
+        ));
+        assert!(matches!(db.complete(b, 0), Err(Error::Blocked(_))));
+        db.update_status(b, TaskStatus::Cancelled).unwrap();
+
+        db.conn
+            .execute(
+                "INSERT INTO TASK_STATUS(TASK_ID, STATUS, UPDATED) VALUES(?, ?, ?)",
+                (a, TaskStatus::Complete as u8, i64::MAX - 1),
+            )
+            .unwrap();
+        assert!(db.open_blockers(b).unwrap().is_empty());
+        db.update_status(b, TaskStatus::InProgress).unwrap();
+    }
+
+    fn stack(db: &Db) -> Vec<TaskId> {
+        db.get_top_n_tasks(10)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_recurrence() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        let chore = db.push_task("water plants".to_string()).unwrap();
+        let b = db.push_task("b".to_string()).unwrap();
+        db.update_content(chore, Some("all of them".to_string()), None)
+            .unwrap();
+        tag(&db, chore, "home");
+        let today = Local::now().date_naive();
+        db.set_due(chore, Some(start_of_day(today))).unwrap();
+        db.set_recurrence(chore, Some(&Recurrence::AfterDays(3)))
+            .unwrap();
+        assert_eq!(
+            db.get_task(chore).unwrap().recurrence,
+            Some(Recurrence::AfterDays(3))
+        );
+
+        let next = db.complete(chore, 1).unwrap().unwrap();
+        assert_eq!(stack(&db), vec![b, next, a]);
+        let task = db.get_task(next).unwrap();
+        assert_eq!(task.title, "water plants");
+        assert_eq!(
+            task.content.and_then(|c| c.body).as_deref(),
+            Some("all of them")
+        );
+        assert_eq!(task.tags, vec![Tag::from("home".to_string())]);
+        assert_eq!(task.recurrence, Some(Recurrence::AfterDays(3)));
+        assert_eq!(task.due, Some(start_of_day(today + Days::new(3))));
+        // completing it again doesn't repeat it twice
+        assert_eq!(db.complete(chore, 1).unwrap(), None);
+
+        db.undo().unwrap();
+        db.undo().unwrap();
+        assert_eq!(stack(&db), vec![b, chore, a]);
+        assert!(db.get_task(next).is_err());
+
+        db.set_recurrence(chore, None).unwrap();
+        assert_eq!(db.complete(chore, 0).unwrap(), None);
+        assert!(db.set_recurrence(next + 1, None).is_err());
+    }
+
+    #[test]
+    fn test_time_tracking() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("a".to_string()).unwrap();
+        let b = db.create_task("b".to_string()).unwrap();
+        db.conn
+            .execute("UPDATE TASK_STATUS SET UPDATED = 100", ())
+            .unwrap();
+        set_status(&db, a, TaskStatus::InProgress, 200);
+        set_status(&db, a, TaskStatus::Todo, 500);
+        set_status(&db, a, TaskStatus::InProgress, 1000);
+        set_status(&db, a, TaskStatus::Complete, 1600);
+        set_status(&db, b, TaskStatus::InProgress, 1500);
+        let now = DateTime::from_timestamp(2000, 0).unwrap();
+
+        let work = db.get_work(None, now).unwrap();
+        assert_eq!(
+            work.iter()
+                .map(|w| (w.task_id, w.started.timestamp(), w.duration.num_seconds()))
+                .collect::<Vec<_>>(),
+            vec![(a, 200, 300), (a, 1000, 600), (b, 1500, 500)]
+        );
+        assert_eq!(db.time_spent(b, now).unwrap(), Duration::seconds(500));
+
+        assert!(db.pause(a).is_err());
+        assert!(db.resume(b).is_err());
+        db.pause(b).unwrap();
+        assert_eq!(db.get_task(b).unwrap().status, TaskStatus::Todo);
+        assert_eq!(db.last_paused().unwrap(), Some(b));
+        db.resume(b).unwrap();
+        assert_eq!(db.last_paused().unwrap(), None);
+
+        db.log_time(a, now, Duration::minutes(-5)).unwrap();
+        let work = db.get_work(Some(a), now).unwrap();
+        assert!(work[2].manual);
+        assert_eq!(db.time_spent(a, now).unwrap(), Duration::seconds(600));
+        assert!(db.log_time(b + 1, now, Duration::hours(1)).is_err());
+
+        db.log_focus(a, now, Duration::minutes(25)).unwrap();
+        db.log_focus(a, now + Duration::hours(1), Duration::minutes(50))
+            .unwrap();
+        assert_eq!(
+            db.get_focus_sessions(a).unwrap(),
+            vec![
+                (now, Duration::minutes(25)),
+                (now + Duration::hours(1), Duration::minutes(50))
+            ]
+        );
+        assert!(db.get_focus_sessions(b).unwrap().is_empty());
+        // sessions aren't counted twice, the time is tracked from the task's status
+        assert_eq!(db.time_spent(a, now).unwrap(), Duration::seconds(600));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.push_task("a".to_string()).unwrap();
+        let b = db.push_task("b".to_string()).unwrap();
+        let c = db.push_task("c".to_string()).unwrap();
+        assert_eq!(stack(&db), vec![c, b, a]);
+
+        // rot
+        db.set_next_of(c, a).unwrap();
+        assert_eq!(stack(&db), vec![b, a, c]);
+        assert_eq!(db.undo().unwrap().as_deref(), Some("reorder"));
+        assert_eq!(stack(&db), vec![c, b, a]);
+        assert_eq!(db.redo().unwrap().as_deref(), Some("reorder"));
+        assert_eq!(stack(&db), vec![b, a, c]);
+        assert_eq!(db.redo().unwrap(), None);
+
+        assert_eq!(db.complete(b, 0).unwrap(), None);
+        assert_eq!(stack(&db), vec![a, c]);
+        assert_eq!(db.get_task(b).unwrap().status, TaskStatus::Complete);
+        db.undo().unwrap();
+        assert_eq!(stack(&db), vec![b, a, c]);
+        assert_eq!(db.get_task(b).unwrap().status, TaskStatus::Todo);
+
+        // A new operation discards what could have been redone
+        db.update_status(b, TaskStatus::InProgress).unwrap();
+        assert_eq!(db.redo().unwrap(), None);
+
+        db.undo().unwrap();
+        db.undo().unwrap();
+        assert_eq!(stack(&db), vec![c, b, a]);
+        // Logged time is not journaled, so undoing the push has to take it along
+        let now = Utc::now();
+        db.log_time(c, now, Duration::minutes(30)).unwrap();
+        db.log_focus(c, now, Duration::minutes(25)).unwrap();
+        assert_eq!(db.undo().unwrap().as_deref(), Some("push"));
+        assert_eq!(stack(&db), vec![b, a]);
+        assert!(db.get_task(c).is_err());
+        assert!(db
+            .get_work(None, now)
+            .unwrap()
+            .iter()
+            .all(|w| w.task_id != c));
+        assert!(db.get_focus_sessions(c).unwrap().is_empty());
+        db.redo().unwrap();
+        assert_eq!(stack(&db), vec![c, b, a]);
+        assert_eq!(db.get_task(c).unwrap().title, "c");
+        // and redoing the push puts it back
+        let d = db.push_task("d".to_string()).unwrap();
+        db.log_time(d, now, Duration::minutes(30)).unwrap();
+        db.log_focus(d, now, Duration::minutes(25)).unwrap();
+        db.connect(d, BLOCKS, a).unwrap();
+        let pushed = dump_task(&db.conn, d).unwrap();
+        db.undo().unwrap();
+        db.redo().unwrap();
+        assert_eq!(dump_task(&db.conn, d).unwrap(), pushed);
+        assert_eq!(open_blockers(&db.conn, a).unwrap(), vec![d]);
+
+        // Redoing a recurring complete brings the next instance back as it was
+        db.update_content(
+            c,
+            Some("all of them".to_string()),
+            Some("https://example.com".to_string()),
+        )
+        .unwrap();
+        tag(&db, c, "home");
+        db.set_due(c, Some(start_of_day(Local::now().date_naive())))
+            .unwrap();
+        db.set_recurrence(c, Some(&Recurrence::Daily)).unwrap();
+        let next = db.complete(c, 0).unwrap().unwrap();
+        let repeated = dump_task(&db.conn, next).unwrap();
+        assert_eq!(repeated.recurrence, Some(Recurrence::Daily));
+        assert!(repeated.due.is_some());
+        db.undo().unwrap();
+        assert!(db.get_task(next).is_err());
+        db.redo().unwrap();
+        assert_eq!(dump_task(&db.conn, next).unwrap(), repeated);
+        assert_eq!(stack(&db)[0], next);
+    }
+
+    #[test]
+    fn test_migrate_existing_database() {
+        let path = std::env::temp_dir().join(format!("tsk-migrate-{}.db", std::process::id()));
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute("INSERT INTO TASK(TITLE) VALUES('old task')", ())
+            .unwrap();
+        conn.execute("INSERT INTO TASK_STATUS(TASK_ID, STATUS) VALUES(1, 0)", ())
+            .unwrap();
+
+        migrate(&mut conn).unwrap();
+        let version: usize = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        let backups: Vec<_> = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.to_string_lossy()
+                    .starts_with(&format!("{}.v1-", path.display()))
+            })
+            .collect();
+        assert_eq!(backups.len(), 1);
+
+        let mut db = Db { conn };
+        let task_id = db.create_task("new task".to_string()).unwrap();
+        db.update_content(task_id, Some("some notes".to_string()), None)
+            .unwrap();
+        assert_eq!(
+            ids(db.query(&[QueryArgs::Text("old".to_string())]).unwrap()),
+            vec![1]
+        );
+        assert_eq!(
+            ids(db.query(&[QueryArgs::Text("notes".to_string())]).unwrap()),
+            vec![task_id]
+        );
+        drop(db);
+        for p in backups.iter().chain([&path]) {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_refuse_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+
+    fn ids(results: Vec<SearchResult>) -> Vec<TaskId> {
+        results.into_iter().map(|r| r.task.id).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("write the report".to_string()).unwrap();
+        let b = db.create_task("review 100% of PRs".to_string()).unwrap();
+        let c = db.create_task("water plants".to_string()).unwrap();
+        tag(&db, a, "work");
+        tag(&db, b, "work");
+        set_status(&db, b, TaskStatus::InProgress, i64::MAX - 1);
+        set_status(&db, c, TaskStatus::Complete, i64::MAX - 1);
+
+        assert_eq!(ids(db.query(&[]).unwrap()), vec![a, b, c]);
+        let work = Tag::from("work".to_string());
+        assert_eq!(
+            ids(db.query(&[QueryArgs::Tag(false, work.clone())]).unwrap()),
+            vec![a, b]
+        );
+        assert_eq!(
+            ids(db.query(&[QueryArgs::Tag(true, work.clone())]).unwrap()),
+            vec![c]
+        );
+        assert_eq!(
+            ids(db
+                .query(&[QueryArgs::Status(false, TaskStatus::InProgress)])
+                .unwrap()),
+            vec![b]
+        );
+        assert_eq!(
+            ids(db
+                .query(&[
+                    QueryArgs::Tag(false, work),
+                    QueryArgs::Status(true, TaskStatus::InProgress)
+                ])
+                .unwrap()),
+            vec![a]
+        );
+        assert_eq!(
+            ids(db.query(&[QueryArgs::Text("wat".to_string())]).unwrap()),
+            vec![c]
+        );
+    }
+
+    #[test]
+    fn test_full_text_search() {
+        let mut db = Db::in_memory().unwrap();
+        let a = db.create_task("plan the garden".to_string()).unwrap();
+        let b = db.create_task("garden".to_string()).unwrap();
+        let c = db.create_task("buy \"quoted\" seeds".to_string()).unwrap();
+        db.conn
+            .execute("UPDATE TASK SET TITLE = 'fix the gate' WHERE ID = ?", (a,))
+            .unwrap();
+
+        let results = db.query(&[QueryArgs::Text("garden".to_string())]).unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.task.id).collect::<Vec<_>>(),
+            vec![b]
+        );
+        assert_eq!(
+            results[0].snippet.as_deref(),
+            Some(format!("{SNIPPET_START}garden{SNIPPET_END}").as_str())
+        );
+        assert_eq!(
+            ids(db
+                .query(&[QueryArgs::Text("\"quoted".to_string())])
+                .unwrap()),
+            vec![c]
+        );
+        let mut prefixed = ids(db.query(&[QueryArgs::Text("ga".to_string())]).unwrap());
+        prefixed.sort();
+        assert_eq!(prefixed, vec![a, b]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    error::{Error, Result},
+    recurrence::Recurrence,
+    types::{TaskId, TaskStatus, MAX_TIME_ENTRY_HOURS},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// `DUMP_VERSION` is bumped whenever the format changes. Dumps from newer versions are refused
+/// rather than imported with missing data.
+///
+/// - 2: task metadata
+/// - 3: due and scheduled dates
+/// - 4: recurrence rules
+/// - 5: manual time entries
+/// - 6: focus sessions
+pub(crate) const DUMP_VERSION: u32 = 6;
+
+/// `Dump` is a lossless copy of a database, written by `tsk export` and read by `tsk import`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Dump {
+    pub version: u32,
+    /// Task IDs from the top of the stack down, following the NEXT chain
+    pub stack: Vec<TaskId>,
+    /// Every tag, including those no task or relationship uses
+    pub tags: Vec<String>,
+    pub tasks: Vec<DumpTask>,
+    pub relationships: Vec<DumpRelationship>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DumpTask {
+    pub id: TaskId,
+    pub title: String,
+    pub created: DateTime<Utc>,
+    /// Every status the task has been in, oldest first
+    pub statuses: Vec<DumpStatus>,
+    /// Every revision of the task's content, oldest first
+    pub revisions: Vec<DumpRevision>,
+    pub tags: Vec<String>,
+    /// Missing from version 1 dumps
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Missing from dumps before version 3
+    #[serde(default)]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub scheduled: Option<DateTime<Utc>>,
+    /// Missing from dumps before version 4
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Time logged by hand, missing from dumps before version 5. Tracked time is kept in
+    /// `statuses`
+    #[serde(default)]
+    pub time_entries: Vec<DumpTimeEntry>,
+    /// Finished focus sessions, missing from dumps before version 6
+    #[serde(default)]
+    pub focus_sessions: Vec<DumpTimeEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DumpStatus {
+    pub status: TaskStatus,
+    pub updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DumpRevision {
+    pub updated: DateTime<Utc>,
+    pub body: Option<String>,
+    /// Kept as written rather than parsed, so links that no longer parse survive a round trip
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DumpTimeEntry {
+    pub started: DateTime<Utc>,
+    /// Negative for corrections that take time off
+    pub seconds: i64,
+}
+
+/// `left` `tag` `right`, e.g. `tsk-1 blocks tsk-2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DumpRelationship {
+    pub left: TaskId,
+    pub tag: String,
+    pub right: TaskId,
+}
+
+/// How a dump is written into a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImportMode {
+    /// Delete everything and restore the dump with its original IDs
+    Replace,
+    /// Add the dump's tasks under new IDs, below the existing stack
+    Merge,
+}
+
+impl Dump {
+    /// `new` is an empty dump of the current version, for importers to fill in.
+    pub(crate) fn new() -> Dump {
+        Dump {
+            version: DUMP_VERSION,
+            stack: Vec::new(),
+            tags: Vec::new(),
+            tasks: Vec::new(),
+            relationships: Vec::new(),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Internal(format!("Unable to write export: {e}")))
+    }
+
+    /// `from_json` parses and validates a dump. The version is checked before anything else, so
+    /// a newer dump fails with a clear error instead of a confusing parse error.
+    pub(crate) fn from_json(json: &str) -> Result<Dump> {
+        let invalid = |e: serde_json::Error| Error::Parse(format!("Invalid export file: {e}"));
+        let value: serde_json::Value = serde_json::from_str(json).map_err(invalid)?;
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version > DUMP_VERSION as u64 => {
+                return Err(Error::Parse(format!(
+                    "The export file is version {version}, this tsk only reads up to version \
+                     {DUMP_VERSION}"
+                )))
+            }
+            Some(_) => {}
+            None => {
+                return Err(Error::Parse(
+                    "Invalid export file: missing version".to_string(),
+                ))
+            }
+        }
+        let dump: Dump = serde_json::from_value(value).map_err(invalid)?;
+        dump.validate()?;
+        Ok(dump)
+    }
+
+    /// `validate` checks that every ID in the dump refers to one of its tasks, so an import
+    /// can't leave dangling references behind.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let invalid =
+            |message: String| Err(Error::Parse(format!("Invalid export file: {message}")));
+        let mut ids = HashSet::new();
+        for task in &self.tasks {
+            if task.id == 0 {
+                return invalid("task ID 0 is reserved".to_string());
+            }
+            if !ids.insert(task.id) {
+                return invalid(format!("TSK-{} appears more than once", task.id));
+            }
+            let longest = Duration::hours(MAX_TIME_ENTRY_HOURS).num_seconds();
+            if task.time_entries.iter().any(|e| e.seconds.abs() > longest) {
+                return invalid(format!(
+                    "TSK-{} has a time entry over {MAX_TIME_ENTRY_HOURS} hours",
+                    task.id
+                ));
+            }
+        }
+        let mut stacked = HashSet::new();
+        for task_id in &self.stack {
+            if !ids.contains(task_id) {
+                return invalid(format!("the stack refers to unknown TSK-{task_id}"));
+            }
+            if !stacked.insert(task_id) {
+                return invalid(format!("TSK-{task_id} is on the stack more than once"));
+            }
+        }
+        for r in &self.relationships {
+            for task_id in [r.left, r.right] {
+                if !ids.contains(&task_id) {
+                    return invalid(format!(
+                        "relationship TSK-{} {} TSK-{} refers to unknown TSK-{task_id}",
+                        r.left, r.tag, r.right
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dump() -> Dump {
+        let created = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        Dump {
+            version: DUMP_VERSION,
+            stack: vec![2, 1],
+            tags: vec!["blocks".to_string(), "work".to_string()],
+            tasks: [1, 2]
+                .into_iter()
+                .map(|id| DumpTask {
+                    id,
+                    title: format!("task {id}"),
+                    created,
+                    statuses: vec![DumpStatus {
+                        status: TaskStatus::InProgress,
+                        updated: created,
+                    }],
+                    revisions: vec![DumpRevision {
+                        updated: created,
+                        body: Some("body".to_string()),
+                        link: None,
+                    }],
+                    tags: vec!["work".to_string()],
+                    metadata: BTreeMap::from([("due".to_string(), "2023-12-01".to_string())]),
+                    due: Some(created),
+                    scheduled: None,
+                    recurrence: Some(Recurrence::AfterDays(10)),
+                    time_entries: vec![DumpTimeEntry {
+                        started: created,
+                        seconds: -900,
+                    }],
+                    focus_sessions: vec![DumpTimeEntry {
+                        started: created,
+                        seconds: 1500,
+                    }],
+                })
+                .collect(),
+            relationships: vec![DumpRelationship {
+                left: 1,
+                tag: "blocks".to_string(),
+                right: 2,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = dump().to_json().unwrap();
+        assert!(json.contains("\"status\": \"inprogress\""));
+        assert!(json.contains("\"created\": \"2023-11-14T22:13:20Z\""));
+        assert!(json.contains("\"recurrence\": \"every 10 days\""));
+        assert_eq!(Dump::from_json(&json).unwrap(), dump());
+    }
+
+    #[test]
+    fn test_version_1() {
+        let mut old = dump();
+        old.version = 1;
+        let json = old.to_json().unwrap().replace(
+            ",\n      \"metadata\": {\n        \"due\": \"2023-12-01\"\n      }",
+            "",
+        );
+        assert!(!json.contains("metadata"));
+        let read = Dump::from_json(&json).unwrap();
+        assert!(read.tasks.iter().all(|task| task.metadata.is_empty()));
+    }
+
+    #[test]
+    fn test_invalid_dump() {
+        let error = |dump: Dump| match Dump::from_json(&dump.to_json().unwrap()) {
+            Err(Error::Parse(e)) => e,
+            other => panic!("expected a parse error, got {other:?}"),
+        };
+        let mut newer = dump();
+        newer.version = DUMP_VERSION + 1;
+        assert!(error(newer).contains(&format!("only reads up to version {DUMP_VERSION}")));
+        let mut unknown = dump();
+        unknown.stack.push(7);
+        assert!(error(unknown).contains("unknown TSK-7"));
+        let mut duplicate = dump();
+        duplicate.tasks[1].id = 1;
+        assert!(error(duplicate).contains("TSK-1 appears more than once"));
+        let mut dangling = dump();
+        dangling.relationships[0].right = 3;
+        assert!(error(dangling).contains("refers to unknown TSK-3"));
+        let json = dump().to_json().unwrap().replace("every 10 days", "hourly");
+        assert!(Dump::from_json(&json).is_err_and(|e| e.to_string().contains("hourly")));
+        assert!(Dump::from_json("{\"tasks\": []}")
+            .is_err_and(|e| e.to_string().contains("missing version")));
+    }
+}
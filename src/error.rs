@@ -1,13 +1,37 @@
+use std::fmt::Display;
 use std::io::Error as IOError;
 use uris::Error as URIError;
 
 #[derive(Debug)]
 pub(super) enum Error {
     Config(IOError),
+    /// The config file couldn't be parsed or has an invalid setting
+    InvalidConfig(String),
     Database(String),
     Internal(String),
     Bug(String),
-    URIFormat(URIError)
+    Parse(String),
+    /// A blocked task can't be started or completed
+    Blocked(String),
+    /// A task given by ID or stack position doesn't exist
+    NotFound(String),
+    URIFormat(URIError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "Configuration error: {e}"),
+            Error::InvalidConfig(e) => write!(f, "Invalid configuration: {e}"),
+            Error::Database(e) => write!(f, "{e}"),
+            Error::Internal(e) => write!(f, "Internal error: {e}"),
+            Error::Bug(e) => write!(f, "{e}"),
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::Blocked(e) => write!(f, "{e}"),
+            Error::NotFound(e) => write!(f, "{e}"),
+            Error::URIFormat(e) => write!(f, "Invalid URI: {e:?}"),
+        }
+    }
 }
 
 pub(super) type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,539 @@
+use crate::{
+    db::BLOCKS,
+    dump::{Dump, DumpRelationship, DumpRevision, DumpStatus, DumpTask},
+    error::{Error, Result},
+    types::{local_date, start_of_day, TaskId, TaskStatus},
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// The metadata key an imported task's UID is kept under.
+const UID: &str = "uid";
+/// RFC 9253's relationship type for a task that can't start before another one is done.
+const DEPENDS_ON: &str = "DEPENDS-ON";
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const DATE_FORMAT: &str = "%Y%m%d";
+/// Content lines are folded after this many bytes.
+const LINE_LENGTH: usize = 75;
+
+/// `uid` is the UID of a task: the one it was imported with, or one made up from its ID and
+/// creation time, which stays the same across exports and is unlikely to clash with another
+/// database's.
+fn uid(task: &DumpTask) -> String {
+    match task.metadata.get(UID) {
+        Some(uid) => uid.clone(),
+        None => format!("tsk-{}-{}@tsk", task.id, task.created.timestamp()),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some(escaped @ ('\\' | ';' | ','))) => {
+                out.push(escaped);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `split_list` splits a comma separated list such as CATEGORIES, ignoring escaped commas.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            ',' if !escaped => items.push(String::new()),
+            _ => items.last_mut().unwrap().push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    items
+        .iter()
+        .map(|item| unescape(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// `fold` writes a content line, breaking it into lines of at most 75 bytes as RFC 5545 asks.
+fn fold(out: &mut String, line: &str) {
+    let mut start = 0;
+    let mut limit = LINE_LENGTH;
+    for (i, c) in line.char_indices() {
+        if i + c.len_utf8() - start > limit {
+            out.push_str(&line[start..i]);
+            out.push_str("\r\n ");
+            start = i;
+            // the leading space counts towards the limit
+            limit = LINE_LENGTH - 1;
+        }
+    }
+    out.push_str(&line[start..]);
+    out.push_str("\r\n");
+}
+
+/// `from_dump` writes `dump` as an iCalendar file with a VTODO per task, the stack first.
+pub(crate) fn from_dump(dump: &Dump) -> String {
+    let tasks: HashMap<TaskId, &DumpTask> = dump.tasks.iter().map(|t| (t.id, t)).collect();
+    let stacked: HashSet<TaskId> = dump.stack.iter().copied().collect();
+    let mut out = String::new();
+    for line in ["BEGIN:VCALENDAR", "VERSION:2.0", "PRODID:-//tsk//tsk//EN"] {
+        fold(&mut out, line);
+    }
+    let ordered = dump
+        .stack
+        .iter()
+        .map(|id| tasks[id])
+        .chain(dump.tasks.iter().filter(|t| !stacked.contains(&t.id)));
+    for task in ordered {
+        let time = |time: DateTime<Utc>| time.format(TIME_FORMAT).to_string();
+        let mut lines = vec![
+            "BEGIN:VTODO".to_string(),
+            format!("UID:{}", escape(&uid(task))),
+            format!("DTSTAMP:{}", time(task.created)),
+            format!("CREATED:{}", time(task.created)),
+            format!("SUMMARY:{}", escape(&task.title)),
+        ];
+        let latest = task.statuses.iter().max_by_key(|s| s.updated);
+        let status = latest.map(|s| s.status).unwrap_or_default();
+        let modified = task
+            .statuses
+            .iter()
+            .map(|s| s.updated)
+            .chain(task.revisions.iter().map(|r| r.updated))
+            .max()
+            .unwrap_or(task.created);
+        lines.push(format!("LAST-MODIFIED:{}", time(modified)));
+        lines.push(format!(
+            "STATUS:{}",
+            match status {
+                TaskStatus::Todo | TaskStatus::Hidden => "NEEDS-ACTION",
+                TaskStatus::InProgress => "IN-PROCESS",
+                TaskStatus::Complete => "COMPLETED",
+                TaskStatus::Cancelled => "CANCELLED",
+            }
+        ));
+        if let (TaskStatus::Complete, Some(latest)) = (status, latest) {
+            lines.push(format!("COMPLETED:{}", time(latest.updated)));
+        }
+        // tsk dates are whole days
+        let date = |time: DateTime<Utc>| local_date(time).format(DATE_FORMAT).to_string();
+        if let Some(scheduled) = task.scheduled {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", date(scheduled)));
+        }
+        if let Some(due) = task.due {
+            lines.push(format!("DUE;VALUE=DATE:{}", date(due)));
+        }
+        if let Some(revision) = task.revisions.iter().max_by_key(|r| r.updated) {
+            if let Some(body) = &revision.body {
+                lines.push(format!("DESCRIPTION:{}", escape(body)));
+            }
+            if let Some(link) = &revision.link {
+                lines.push(format!("URL:{link}"));
+            }
+        }
+        if !task.tags.is_empty() {
+            let tags = task.tags.iter().map(|t| escape(t)).collect::<Vec<_>>();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        for r in &dump.relationships {
+            // a blocked task depends on its blocker, other relationships are written on their
+            // left task, e.g. `RELTYPE=X-FOLLOWS` for `tsk-1 follows tsk-2`
+            if r.tag == BLOCKS && r.right == task.id {
+                lines.push(format!(
+                    "RELATED-TO;RELTYPE={DEPENDS_ON}:{}",
+                    escape(&uid(tasks[&r.left]))
+                ));
+            } else if r.tag != BLOCKS && r.left == task.id {
+                lines.push(format!(
+                    "RELATED-TO;RELTYPE=X-{}:{}",
+                    r.tag.to_uppercase(),
+                    escape(&uid(tasks[&r.right]))
+                ));
+            }
+        }
+        lines.push("END:VTODO".to_string());
+        for line in lines {
+            fold(&mut out, &line);
+        }
+    }
+    fold(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A content line, `NAME;PARAM=VALUE:value`.
+#[derive(Debug, PartialEq, Eq)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // the value starts at the first colon outside a quoted parameter value
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(n, v)| (n.to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+/// `parse_time` reads a DATE-TIME in UTC, a floating DATE-TIME or a DATE. Anything not in UTC
+/// is taken as local time.
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    let local = |time: NaiveDateTime| {
+        time.and_local_timezone(Local)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| time.and_utc())
+    };
+    NaiveDateTime::parse_from_str(value, TIME_FORMAT)
+        .map(|t| t.and_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map(local))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, DATE_FORMAT)
+                .map(|d| local(d.and_hms_opt(0, 0, 0).unwrap()))
+        })
+        .map_err(|_| Error::Parse(format!("Invalid iCalendar time \"{value}\"")))
+}
+
+/// The VTODOs of a calendar, as lists of properties.
+fn components(text: &str) -> Result<Vec<Vec<Property>>> {
+    // unfold lines continued with a leading space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut todos = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    // how deep inside the current VTODO a component such as a VALARM is nested, whose
+    // properties aren't the task's
+    let mut nested = 0;
+    for line in lines.iter().filter(|l| !l.trim().is_empty()) {
+        let property = parse_property(line)
+            .ok_or_else(|| Error::Parse(format!("Invalid iCalendar line \"{line}\"")))?;
+        match (
+            property.name.as_str(),
+            property.value.to_uppercase().as_str(),
+        ) {
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if nested > 0 => nested -= 1,
+            ("BEGIN", "VTODO") => current = Some(Vec::new()),
+            ("END", "VTODO") => todos.extend(current.take()),
+            _ => {
+                if let Some(todo) = current.as_mut().filter(|_| nested == 0) {
+                    todo.push(property);
+                }
+            }
+        }
+    }
+    Ok(todos)
+}
+
+/// `to_dump` converts the VTODOs of an iCalendar file into a [`Dump`] to merge into the
+/// database, skipping those whose UID already belongs to a task in `existing`, so importing the
+/// same file twice doesn't duplicate anything. The UIDs are kept as metadata.
+///
+/// Open tasks are put on the stack in the order they appear. Relationships to tasks outside the
+/// file can't be restored and are dropped.
+pub(crate) fn to_dump(text: &str, existing: &Dump) -> Result<Dump> {
+    let mut seen: HashSet<String> = existing.tasks.iter().map(uid).collect();
+    let mut dump = Dump::new();
+    let mut ids: HashMap<String, TaskId> = HashMap::new();
+    let mut related = Vec::new();
+    let mut tags = BTreeSet::new();
+    let now = Utc::now();
+    for todo in components(text)? {
+        let get = |name: &str| todo.iter().find(|p| p.name == name);
+        let uid = match get("UID") {
+            Some(p) => unescape(&p.value),
+            None => return Err(Error::Parse("A VTODO has no UID".to_string())),
+        };
+        if !seen.insert(uid.clone()) {
+            continue;
+        }
+        let id = dump.tasks.len() as TaskId + 1;
+        ids.insert(uid.clone(), id);
+        let time = |name: &str| get(name).map(|p| parse_time(&p.value)).transpose();
+        let created = time("CREATED")?.or(time("DTSTAMP")?).unwrap_or(now);
+        let modified = time("LAST-MODIFIED")?.unwrap_or(created).max(created);
+        let status = match get("STATUS").map(|p| p.value.to_uppercase()).as_deref() {
+            Some("IN-PROCESS") => TaskStatus::InProgress,
+            Some("COMPLETED") => TaskStatus::Complete,
+            Some("CANCELLED") => TaskStatus::Cancelled,
+            _ => TaskStatus::Todo,
+        };
+        let mut statuses = vec![DumpStatus {
+            status: TaskStatus::Todo,
+            updated: created,
+        }];
+        if status != TaskStatus::Todo {
+            let updated = match status {
+                TaskStatus::Complete => time("COMPLETED")?.unwrap_or(modified),
+                _ => modified,
+            };
+            statuses.push(DumpStatus {
+                status,
+                updated: updated.max(created),
+            });
+        }
+        if matches!(status, TaskStatus::Todo | TaskStatus::InProgress) {
+            dump.stack.push(id);
+        }
+        let body = get("DESCRIPTION").map(|p| unescape(&p.value));
+        let link = get("URL").map(|p| p.value.clone());
+        let revisions = match (&body, &link) {
+            (None, None) => Vec::new(),
+            _ => vec![DumpRevision {
+                updated: modified,
+                body,
+                link,
+            }],
+        };
+        let mut task_tags: Vec<String> = todo
+            .iter()
+            .filter(|p| p.name == "CATEGORIES")
+            .flat_map(|p| split_list(&p.value))
+            .collect();
+        task_tags.sort();
+        task_tags.dedup();
+        tags.extend(task_tags.iter().cloned());
+        for p in todo.iter().filter(|p| p.name == "RELATED-TO") {
+            let other = unescape(&p.value);
+            match p.param("RELTYPE").map(|t| t.to_uppercase()) {
+                Some(t) if t == DEPENDS_ON => {
+                    related.push((other, BLOCKS.to_string(), uid.clone()))
+                }
+                Some(t) if t.starts_with("X-") => {
+                    related.push((uid.clone(), t[2..].to_lowercase(), other))
+                }
+                // PARENT, CHILD and SIBLING have nothing to map to
+                _ => {}
+            }
+        }
+        dump.tasks.push(DumpTask {
+            id,
+            title: get("SUMMARY")
+                .map(|p| unescape(&p.value))
+                .unwrap_or_default(),
+            created,
+            statuses,
+            revisions,
+            tags: task_tags,
+            metadata: BTreeMap::from([(UID.to_string(), uid)]),
+            due: time("DUE")?.map(|t| start_of_day(local_date(t))),
+            scheduled: time("DTSTART")?.map(|t| start_of_day(local_date(t))),
+            ..Default::default()
+        });
+    }
+    for (left, tag, right) in related {
+        if let (Some(&left), Some(&right)) = (ids.get(&left), ids.get(&right)) {
+            tags.insert(tag.clone());
+            dump.relationships
+                .push(DumpRelationship { left, tag, right });
+        }
+    }
+    dump.tags = tags.into_iter().collect();
+    dump.validate()?;
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dump() -> Dump {
+        let time = |t: &str| parse_time(t).unwrap();
+        let task = |id: TaskId, title: &str, status: TaskStatus| {
+            let mut statuses = vec![DumpStatus {
+                status: TaskStatus::Todo,
+                updated: time("20231101T090000Z"),
+            }];
+            if status != TaskStatus::Todo {
+                statuses.push(DumpStatus {
+                    status,
+                    updated: time("20231102T090000Z"),
+                });
+            }
+            DumpTask {
+                id,
+                title: title.to_string(),
+                created: time("20231101T090000Z"),
+                statuses,
+                ..Default::default()
+            }
+        };
+        let mut report = task(1, "Write report; then, send it", TaskStatus::InProgress);
+        report.revisions.push(DumpRevision {
+            updated: time("20231102T090000Z"),
+            body: Some(format!("Numbers:\n{}", "é".repeat(60))),
+            link: Some("https://example.com/report".to_string()),
+        });
+        report.tags = vec!["work".to_string(), "q4,2023".to_string()];
+        report.due = Some(start_of_day(NaiveDate::from_ymd_opt(2023, 11, 10).unwrap()));
+        Dump {
+            stack: vec![1, 2],
+            tags: vec!["blocks".to_string(), "follows".to_string()],
+            tasks: vec![
+                report,
+                task(2, "Collect numbers", TaskStatus::Todo),
+                task(3, "Book flights", TaskStatus::Complete),
+            ],
+            relationships: vec![
+                DumpRelationship {
+                    left: 2,
+                    tag: BLOCKS.to_string(),
+                    right: 1,
+                },
+                DumpRelationship {
+                    left: 3,
+                    tag: "follows".to_string(),
+                    right: 1,
+                },
+            ],
+            ..Dump::new()
+        }
+    }
+
+    #[test]
+    fn test_from_dump() {
+        let ics = from_dump(&dump());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= LINE_LENGTH));
+        for line in [
+            "UID:tsk-1-1698829200@tsk",
+            "DTSTAMP:20231101T090000Z",
+            "SUMMARY:Write report\\; then\\, send it",
+            "STATUS:IN-PROCESS",
+            "URL:https://example.com/report",
+            "CATEGORIES:work,q4\\,2023",
+            "RELATED-TO;RELTYPE=DEPENDS-ON:tsk-2-1698829200@tsk",
+            "RELATED-TO;RELTYPE=X-FOLLOWS:tsk-1-1698829200@tsk",
+            "STATUS:COMPLETED",
+            "COMPLETED:20231102T090000Z",
+            "DUE;VALUE=DATE:20231110",
+        ] {
+            assert!(ics.contains(&format!("\r\n{line}\r\n")), "missing {line}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let imported = to_dump(&from_dump(&dump()), &Dump::new()).unwrap();
+        let mut expected = dump();
+        for task in &mut expected.tasks {
+            task.metadata.insert(UID.to_string(), uid(task));
+            task.tags.sort();
+        }
+        expected.tags = vec![
+            "blocks".to_string(),
+            "follows".to_string(),
+            "q4,2023".to_string(),
+            "work".to_string(),
+        ];
+        assert_eq!(imported, expected);
+    }
+
+    #[test]
+    fn test_to_dump() {
+        let ics = "BEGIN:VCALENDAR\r
+BEGIN:VTODO\r
+UID:a@example.com\r
+DTSTAMP:20231101T090000Z\r
+SUMMARY:Renew\r
+  passport\r
+DUE;VALUE=DATE:20231201\r
+CATEGORIES:home,errands\r
+CATEGORIES:home\r
+RELATED-TO;RELTYPE=PARENT:b@example.com\r
+ATTACH;FMTTYPE=\"text/plain\";X-NOTE=\"a:b\":https://example.com/form\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:b@example.com\r
+DTSTAMP:20231020\r
+SUMMARY:Travel\r
+STATUS:CANCELLED\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Default Mozilla Description\r
+TRIGGER;VALUE=DURATION:-PT15M\r
+END:VALARM\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+        let imported = to_dump(ics, &Dump::new()).unwrap();
+        assert_eq!(imported.stack, vec![1]);
+        assert_eq!(imported.tags, vec!["errands", "home"]);
+        assert!(imported.relationships.is_empty());
+        assert_eq!(imported.tasks[0].title, "Renew passport");
+        assert_eq!(imported.tasks[0].metadata[UID], "a@example.com");
+        assert!(imported.tasks[0].revisions.is_empty());
+        assert_eq!(
+            imported.tasks[0].due,
+            Some(start_of_day(NaiveDate::from_ymd_opt(2023, 12, 1).unwrap()))
+        );
+        assert_eq!(
+            imported.tasks[1].statuses.last().unwrap().status,
+            TaskStatus::Cancelled
+        );
+        // the alarm's description isn't the task's body
+        assert!(imported.tasks[1].revisions.is_empty());
+
+        // importing again skips tasks that are already there
+        let again = to_dump(ics, &imported).unwrap();
+        assert!(again.tasks.is_empty());
+        let ours = to_dump(&from_dump(&dump()), &dump()).unwrap();
+        assert!(ours.tasks.is_empty());
+
+        assert!(to_dump("BEGIN:VTODO\r\nSUMMARY:x\r\nEND:VTODO\r\n", &Dump::new()).is_err());
+        assert!(to_dump(
+            "BEGIN:VTODO\r\nUID:x\r\nDTSTAMP:soon\r\nEND:VTODO",
+            &Dump::new()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use tui_textarea::{Input, Key};
+
+/// Something a key binding can do, independent of the view it's bound in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    Quit,
+    /// Return to the previous view
+    Back,
+    CompleteTop,
+    Swap,
+    OpenDetails,
+    OpenQuery,
+    Undo,
+}
+
+impl Action {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Back => "back",
+            Action::CompleteTop => "complete_top",
+            Action::Swap => "swap",
+            Action::OpenDetails => "open_details",
+            Action::OpenQuery => "open_query",
+            Action::Undo => "undo",
+        }
+    }
+}
+
+/// The views that have their own keymap layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum View {
+    Home,
+    Details,
+    Backlog,
+    Query,
+}
+
+const VIEWS: [View; 4] = [View::Home, View::Details, View::Backlog, View::Query];
+
+impl View {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            View::Home => "home",
+            View::Details => "details",
+            View::Backlog => "backlog",
+            View::Query => "query",
+        }
+    }
+
+    /// `defaults` is every action the view supports along with its default keys.
+    fn defaults(&self) -> &'static [(Action, &'static [&'static str])] {
+        match self {
+            View::Home => &[
+                (Action::Quit, &["esc", "ctrl-q"]),
+                (Action::CompleteTop, &["ctrl-t"]),
+                (Action::Swap, &["ctrl-s"]),
+                (Action::OpenDetails, &["ctrl-o"]),
+                (Action::OpenQuery, &["ctrl-g"]),
+                (Action::Undo, &["ctrl-z"]),
+            ],
+            View::Backlog => &[
+                (Action::Back, &["esc"]),
+                (Action::Quit, &["ctrl-q"]),
+                (Action::OpenDetails, &["ctrl-o"]),
+            ],
+            View::Details | View::Query => &[(Action::Back, &["esc"]), (Action::Quit, &["ctrl-q"])],
+        }
+    }
+}
+
+/// A key along with the modifiers that must be held for it. Shift is ignored, letters are
+/// matched case insensitively.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct KeyBinding {
+    key: Key,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl KeyBinding {
+    fn matches(&self, input: &Input) -> bool {
+        let key = match input.key {
+            Key::Char(c) => Key::Char(c.to_ascii_lowercase()),
+            key => key,
+        };
+        self.key == key && self.ctrl == input.ctrl && self.alt == input.alt
+    }
+
+    /// Keys the command editor needs for typing and editing can't be bound without a modifier.
+    fn is_reserved(&self) -> bool {
+        !self.ctrl
+            && !self.alt
+            && matches!(
+                self.key,
+                Key::Char(_)
+                    | Key::Enter
+                    | Key::Backspace
+                    | Key::Delete
+                    | Key::Left
+                    | Key::Right
+                    | Key::Up
+                    | Key::Down
+                    | Key::Home
+                    | Key::End
+            )
+    }
+}
+
+const NAMED_KEYS: [(&str, Key); 13] = [
+    ("esc", Key::Esc),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+];
+
+/// Parses bindings such as `esc`, `ctrl-q`, `alt-enter` or `f5`.
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (mut ctrl, mut alt) = (false, false);
+        let mut rest = lower.as_str();
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl-") {
+                (ctrl, rest) = (true, r);
+            } else if let Some(r) = rest.strip_prefix("alt-") {
+                (alt, rest) = (true, r);
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => Key::Char(c),
+            _ => match NAMED_KEYS.iter().find(|(name, _)| *name == rest) {
+                Some((_, key)) => *key,
+                None => match rest.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=12) => Key::F(n),
+                    _ => return Err(format!("unknown key \"{s}\"")),
+                },
+            },
+        };
+        Ok(KeyBinding { key, ctrl, alt })
+    }
+}
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl-")?;
+        }
+        if self.alt {
+            write!(f, "alt-")?;
+        }
+        match self.key {
+            Key::Char(c) => write!(f, "{c}"),
+            Key::F(n) => write!(f, "f{n}"),
+            key => match NAMED_KEYS.iter().find(|(_, k)| *k == key) {
+                Some((name, _)) => write!(f, "{name}"),
+                None => write!(f, "{key:?}"),
+            },
+        }
+    }
+}
+
+/// The key bindings of a single view.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Layer {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Layer {
+    /// `action` is the action bound to `input`, if any.
+    pub(crate) fn action(&self, input: &Input) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(input))
+            .map(|(_, action)| *action)
+    }
+
+    /// `new` builds the layer for `view` from its defaults, replacing the keys of any action
+    /// in `overrides`. Every key may only be bound to one action.
+    fn new(view: View, overrides: &HashMap<String, Vec<String>>) -> Result<Layer> {
+        let invalid =
+            |message: String| Error::InvalidConfig(format!("keys.{}{message}", view.name()));
+        let defaults = view.defaults();
+        for name in overrides.keys() {
+            if !defaults.iter().any(|(action, _)| action.name() == name) {
+                let actions = defaults
+                    .iter()
+                    .map(|(action, _)| action.name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(invalid(format!(
+                    ".{name}: unknown action, expected one of {actions}"
+                )));
+            }
+        }
+        let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+        for (action, default_keys) in defaults {
+            let keys = match overrides.get(action.name()) {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => default_keys.to_vec(),
+            };
+            for key in keys {
+                let binding = key
+                    .parse::<KeyBinding>()
+                    .map_err(|e| invalid(format!(".{}: {e}", action.name())))?;
+                if binding.is_reserved() {
+                    return Err(invalid(format!(
+                        ".{}: {binding} is reserved for the command editor",
+                        action.name()
+                    )));
+                }
+                if let Some((_, other)) = bindings.iter().find(|(b, _)| *b == binding) {
+                    return Err(invalid(format!(
+                        ": {binding} is bound to both {} and {}",
+                        other.name(),
+                        action.name()
+                    )));
+                }
+                bindings.push((binding, *action));
+            }
+        }
+        Ok(Layer { bindings })
+    }
+}
+
+/// A keymap layer for each view. Keys are looked up in the active view's layer before they
+/// reach the command editor.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Keymap {
+    pub home: Layer,
+    pub details: Layer,
+    pub backlog: Layer,
+    pub query: Layer,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&HashMap::new()).expect("the default keymap has no conflicts")
+    }
+}
+
+impl Keymap {
+    /// `new` builds the keymap from the `[keys.<view>]` tables of the config file, which map
+    /// action names to the keys that trigger them.
+    pub(crate) fn new(overrides: &HashMap<String, HashMap<String, Vec<String>>>) -> Result<Keymap> {
+        for view in overrides.keys() {
+            if !VIEWS.iter().any(|v| v.name() == view) {
+                return Err(Error::InvalidConfig(format!(
+                    "keys.{view}: unknown view, expected one of home, details, backlog, query"
+                )));
+            }
+        }
+        let no_overrides = HashMap::new();
+        let layer =
+            |view: View| Layer::new(view, overrides.get(view.name()).unwrap_or(&no_overrides));
+        Ok(Keymap {
+            home: layer(View::Home)?,
+            details: layer(View::Details)?,
+            backlog: layer(View::Backlog)?,
+            query: layer(View::Query)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn input(key: Key, ctrl: bool) -> Input {
+        Input {
+            key,
+            ctrl,
+            ..Default::default()
+        }
+    }
+
+    fn overrides(
+        view: &str,
+        action: &str,
+        keys: &[&str],
+    ) -> HashMap<String, HashMap<String, Vec<String>>> {
+        HashMap::from([(
+            view.to_string(),
+            HashMap::from([(
+                action.to_string(),
+                keys.iter().map(|k| k.to_string()).collect(),
+            )]),
+        )])
+    }
+
+    fn error(overrides: HashMap<String, HashMap<String, Vec<String>>>) -> String {
+        match Keymap::new(&overrides) {
+            Err(Error::InvalidConfig(e)) => e,
+            other => panic!("expected a config error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_key_binding() {
+        for key in ["esc", "ctrl-q", "alt-enter", "ctrl-alt-x", "f5", "pagedown"] {
+            assert_eq!(key.parse::<KeyBinding>().unwrap().to_string(), key);
+        }
+        assert_eq!(
+            "Ctrl-Q".parse::<KeyBinding>(),
+            Ok(KeyBinding {
+                key: Key::Char('q'),
+                ctrl: true,
+                alt: false
+            })
+        );
+        assert!("ctrl-".parse::<KeyBinding>().is_err());
+        assert!("f13".parse::<KeyBinding>().is_err());
+        assert!("hyper-x".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.home.action(&input(Key::Esc, false)),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            keymap.home.action(&input(Key::Char('Q'), true)),
+            Some(Action::Quit)
+        );
+        assert_eq!(keymap.home.action(&input(Key::Char('q'), false)), None);
+        assert_eq!(
+            keymap.details.action(&input(Key::Esc, false)),
+            Some(Action::Back)
+        );
+        assert_eq!(
+            keymap.backlog.action(&input(Key::Char('o'), true)),
+            Some(Action::OpenDetails)
+        );
+        assert_eq!(keymap.query.action(&input(Key::Char('o'), true)), None);
+    }
+
+    #[test]
+    fn test_override_keymap() {
+        let keymap = Keymap::new(&overrides("home", "undo", &["ctrl-u", "f2"])).unwrap();
+        assert_eq!(keymap.home.action(&input(Key::Char('z'), true)), None);
+        assert_eq!(
+            keymap.home.action(&input(Key::Char('u'), true)),
+            Some(Action::Undo)
+        );
+        assert_eq!(
+            keymap.home.action(&input(Key::F(2), false)),
+            Some(Action::Undo)
+        );
+        // other views keep their defaults
+        assert_eq!(
+            keymap.details.action(&input(Key::Esc, false)),
+            Some(Action::Back)
+        );
+        let keymap = Keymap::new(&overrides("home", "quit", &[])).unwrap();
+        assert_eq!(keymap.home.action(&input(Key::Esc, false)), None);
+    }
+
+    #[test]
+    fn test_invalid_keymap() {
+        assert!(error(overrides("home", "undo", &["ctrl-s"]))
+            .contains("keys.home: ctrl-s is bound to both swap and undo"));
+        assert!(
+            error(overrides("home", "undo", &["ctrl-u", "ctrl-u"])).contains("both undo and undo")
+        );
+        assert!(error(overrides("home", "undo", &["u"])).contains("reserved"));
+        assert!(
+            error(overrides("home", "undo", &["ctrl-"])).contains("keys.home.undo: unknown key")
+        );
+        assert!(error(overrides("details", "swap", &["ctrl-s"])).contains("unknown action"));
+        assert!(error(overrides("settings", "quit", &["ctrl-q"])).contains("unknown view"));
+    }
+}
@@ -1,13 +1,29 @@
+mod cli;
 mod commands;
 mod config;
 mod db;
+mod diff;
+mod dump;
 mod error;
+mod ical;
+mod keymap;
+mod markdown;
+mod recurrence;
+mod taskwarrior;
+mod theme;
+mod timesheet;
+mod todotxt;
 mod types;
 mod views;
+use crate::cli::{exit_code, Cli};
 use crate::error::Result;
+use crate::views::backlog::render_backlog;
+use crate::views::details::render_details;
 use crate::views::home::{render_home, AppState};
+use crate::views::query::render_query;
 use crate::{config::Config, db::Db};
 //use chrono::{DateTime, Utc};
+use clap::Parser;
 use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
@@ -16,23 +32,43 @@ use log::debug;
 use ratatui::prelude::{CrosstermBackend, Terminal};
 use std::io::stdout;
 
-fn main() -> Result<()> {
+fn main() {
     env_logger::init();
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("tsk: {e}");
+        std::process::exit(exit_code(&e));
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let config = Config::load()?;
     debug!("Initializing db...");
-    let mut db = Db::new()?;
-    let config = Config::default();
+    let mut db = Db::new(&config.database_file()?)?;
     debug!("Initialized db.");
+    match cli.command {
+        Some(command) => cli::run(command, cli.json, &mut db, &config),
+        None => run_tui(&mut db, &config),
+    }
+}
+
+/// `run_tui` runs the interactive views until the user exits.
+fn run_tui(db: &mut Db, config: &Config) -> Result<()> {
     stdout().execute(EnterAlternateScreen)?;
     enable_raw_mode()?;
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
     let mut next = AppState::Home;
+    let mut focus = None;
     loop {
         match next {
-            AppState::Home => next = render_home(&mut terminal, &mut db, &config)?,
-            AppState::Details => todo!(),
-            AppState::Query => todo!(),
+            AppState::Home => next = render_home(&mut terminal, db, config, &mut focus)?,
+            AppState::Details(task_id) => {
+                next = render_details(&mut terminal, db, config, task_id)?
+            }
+            AppState::Query(query) => next = render_query(&mut terminal, db, config, query)?,
+            AppState::Backlog => next = render_backlog(&mut terminal, db, config)?,
             AppState::Exit => break,
         }
     }
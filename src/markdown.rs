@@ -0,0 +1,98 @@
+use crate::{
+    config::{Config, MAX_TOP_TASKS},
+    db::Db,
+    error::Result,
+    views::format_duration,
+};
+use chrono::{Local, Utc};
+
+/// `render` writes the stack as a Markdown checklist, in `priority_task` order and with the same
+/// `[ ]`, `[/]` and `[x]` markers as the task list. Each item carries the task's tags, link and
+/// body, and with `history` a list of its status changes.
+pub(crate) fn render(db: &Db, config: &Config, history: bool) -> Result<String> {
+    let now = Utc::now();
+    let mut out = String::from("# Tasks\n");
+    for top in db.get_top_n_tasks(MAX_TOP_TASKS)? {
+        let task = db.get_task(top.id)?;
+        out.push('\n');
+        out.push_str(&format!("- {} TSK-{} {}", task.status, task.id, task.title));
+        for tag in &task.tags {
+            out.push_str(&format!(" {tag}"));
+        }
+        out.push('\n');
+        if let Some(link) = task.content.as_ref().and_then(|c| c.link.as_ref()) {
+            out.push_str(&format!("  <{link}>\n"));
+        }
+        if let Some(body) = task.content.as_ref().and_then(|c| c.body.as_ref()) {
+            out.push('\n');
+            for line in body.lines() {
+                // indented to stay part of the list item
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    out.push_str(&format!("  {line}\n"));
+                }
+            }
+        }
+        if history {
+            out.push_str("\n  History:\n\n");
+            for change in db.get_status_history(task.id)? {
+                out.push_str(&format!(
+                    "  - {} {:?}, {}{}\n",
+                    change
+                        .updated
+                        .with_timezone(&Local)
+                        .format(&config.date_format),
+                    change.status,
+                    format_duration(change.duration(now)),
+                    if change.until.is_none() {
+                        " so far"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::TaskStatus;
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let mut db = Db::in_memory()?;
+        let report = db.push_task("Write report".to_string())?;
+        db.push_task("Call mom".to_string())?;
+        db.create_task("Not on the stack".to_string())?;
+        db.update_content(
+            report,
+            Some("Numbers are in\n\nthe shared folder".to_string()),
+            Some("https://example.com/report".to_string()),
+        )?;
+        db.update_status(report, TaskStatus::InProgress)?;
+        let config = Config::default();
+        assert_eq!(
+            render(&db, &config, false)?,
+            "# Tasks
+
+- [ ] TSK-2 Call mom
+
+- [/] TSK-1 Write report
+  <https://example.com/report>
+
+  Numbers are in
+
+  the shared folder
+"
+        );
+        let with_history = render(&db, &config, true)?;
+        assert!(with_history.contains("\n  History:\n\n  - "));
+        assert!(with_history.contains(" InProgress, 0s so far\n"));
+        assert!(!with_history.contains("Not on the stack"));
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use crate::error::{Error, Result};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// The longest `every N days` rule, ten years.
+const MAX_DAYS: u32 = 3650;
+
+/// How a task repeats. Completing a repeating task creates its next instance, due on the next
+/// day the rule gives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum Recurrence {
+    /// `daily`
+    Daily,
+    /// `weekly` on the weekday the task was due, or `weekly mon,thu` on the given weekdays
+    Weekly(Vec<Weekday>),
+    /// `monthly`, on the day of the month the task was due
+    Monthly,
+    /// `every 10 days`, counted from the day the task is completed
+    AfterDays(u32),
+}
+
+impl Recurrence {
+    /// `next` is the day the next instance is due, given the day the completed one was due and
+    /// today. Calendar rules skip the days that went by while the task was overdue, so the next
+    /// instance is always due after today. It fails when that day is past the last date chrono
+    /// can represent.
+    pub(crate) fn next(&self, due: Option<NaiveDate>, today: NaiveDate) -> Result<NaiveDate> {
+        let start = due.unwrap_or(today);
+        let after = |date: NaiveDate| {
+            match self {
+                Recurrence::Daily => date.checked_add_days(Days::new(1)),
+                Recurrence::Weekly(weekdays) if weekdays.is_empty() => {
+                    date.checked_add_days(Days::new(7))
+                }
+                Recurrence::Weekly(weekdays) => (1..=7)
+                    .map_while(|days| date.checked_add_days(Days::new(days)))
+                    .find(|d| weekdays.contains(&d.weekday())),
+                Recurrence::Monthly => next_month(date, start.day()),
+                Recurrence::AfterDays(days) => date.checked_add_days(Days::new(*days as u64)),
+            }
+            .ok_or_else(|| {
+                Error::Parse(format!(
+                    "The day after {date} repeating {self} is out of range"
+                ))
+            })
+        };
+        if let Recurrence::AfterDays(_) = self {
+            return after(today);
+        }
+        let mut next = after(start)?;
+        while next <= today {
+            next = after(next)?;
+        }
+        Ok(next)
+    }
+}
+
+/// `next_month` is `day` of the month after `date`'s, or the last day of that month if it's
+/// shorter.
+fn next_month(date: NaiveDate, day: u32) -> Option<NaiveDate> {
+    let first = date.with_day(1)?.checked_add_months(Months::new(1))?;
+    Some(
+        (first.day()..=day)
+            .rev()
+            .find_map(|d| first.with_day(d))
+            .unwrap_or(first),
+    )
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => write!(f, "weekly"),
+            Recurrence::Weekly(weekdays) => {
+                let names = weekdays
+                    .iter()
+                    .map(|d| d.to_string().to_lowercase())
+                    .collect::<Vec<_>>();
+                write!(f, "weekly {}", names.join(","))
+            }
+            Recurrence::Monthly => write!(f, "monthly"),
+            Recurrence::AfterDays(1) => write!(f, "every 1 day"),
+            Recurrence::AfterDays(days) => write!(f, "every {days} days"),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Error;
+
+    /// Parses `daily`, `weekly`, `weekly mon,thu` (or `weekly mon thu`), `monthly` and
+    /// `every 10 days`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Parse(format!(
+                "Invalid recurrence `{s}`, expected daily, weekly [mon,thu,...], monthly or \
+                 every N days, N at most {MAX_DAYS}"
+            ))
+        };
+        let lower = s.to_ascii_lowercase();
+        let words = lower
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>();
+        match words.as_slice() {
+            ["daily"] => Ok(Recurrence::Daily),
+            ["monthly"] => Ok(Recurrence::Monthly),
+            ["weekly", weekdays @ ..] => {
+                let mut parsed = weekdays
+                    .iter()
+                    .map(|w| w.parse::<Weekday>().map_err(|_| invalid()))
+                    .collect::<Result<Vec<_>>>()?;
+                parsed.sort_by_key(|d| d.num_days_from_monday());
+                parsed.dedup();
+                Ok(Recurrence::Weekly(parsed))
+            }
+            ["every", days, "day" | "days"] => match days.parse::<u32>() {
+                Ok(days) if (1..=MAX_DAYS).contains(&days) => Ok(Recurrence::AfterDays(days)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        for (input, expected) in [
+            ("daily", Recurrence::Daily),
+            ("Monthly", Recurrence::Monthly),
+            ("weekly", Recurrence::Weekly(vec![])),
+            (
+                "weekly thu, Monday thu",
+                Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]),
+            ),
+            ("every 10 days", Recurrence::AfterDays(10)),
+            ("every 1 day", Recurrence::AfterDays(1)),
+        ] {
+            assert_eq!(input.parse::<Recurrence>().unwrap(), expected);
+            assert_eq!(
+                expected.to_string().parse::<Recurrence>().unwrap(),
+                expected
+            );
+        }
+        assert_eq!(
+            Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]).to_string(),
+            "weekly mon,thu"
+        );
+        for input in [
+            "",
+            "hourly",
+            "weekly someday",
+            "every 0 days",
+            "every days",
+            "every 4000000000 days",
+        ] {
+            assert!(input.parse::<Recurrence>().is_err(), "parsed `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_next() {
+        // 2026-11-02 is a Monday
+        let today = date("2026-11-02");
+        assert_eq!(
+            Recurrence::Daily.next(None, today).unwrap(),
+            date("2026-11-03")
+        );
+        // days missed while overdue are skipped
+        assert_eq!(
+            Recurrence::Daily
+                .next(Some(date("2026-10-20")), today)
+                .unwrap(),
+            date("2026-11-03")
+        );
+        // completing early moves on from the day it was due
+        assert_eq!(
+            Recurrence::Weekly(vec![])
+                .next(Some(date("2026-11-06")), today)
+                .unwrap(),
+            date("2026-11-13")
+        );
+        let mon_thu = Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(mon_thu.next(None, today).unwrap(), date("2026-11-05"));
+        assert_eq!(
+            mon_thu.next(Some(date("2026-11-05")), today).unwrap(),
+            date("2026-11-09")
+        );
+        assert_eq!(
+            Recurrence::Monthly
+                .next(Some(date("2026-10-31")), today)
+                .unwrap(),
+            date("2026-11-30")
+        );
+        assert_eq!(
+            Recurrence::Monthly
+                .next(Some(date("2027-01-31")), today)
+                .unwrap(),
+            date("2027-02-28")
+        );
+        assert_eq!(
+            Recurrence::AfterDays(10)
+                .next(Some(date("2026-12-24")), today)
+                .unwrap(),
+            date("2026-11-12")
+        );
+        assert!(Recurrence::Daily.next(Some(NaiveDate::MAX), today).is_err());
+        assert!(Recurrence::Monthly
+            .next(Some(NaiveDate::MAX), today)
+            .is_err());
+        assert!(Recurrence::AfterDays(MAX_DAYS)
+            .next(None, NaiveDate::MAX)
+            .is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    db::BLOCKS,
+    dump::{Dump, DumpRelationship, DumpRevision, DumpStatus, DumpTask},
+    error::{Error, Result},
+    types::{local_date, start_of_day, TaskId, TaskStatus},
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A task as written by `task export`. Only the attributes tsk has a place for are read.
+#[derive(Debug, Deserialize)]
+struct TwTask {
+    uuid: String,
+    description: String,
+    status: String,
+    entry: String,
+    modified: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    due: Option<String>,
+    scheduled: Option<String>,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    annotations: Vec<TwAnnotation>,
+    depends: Option<TwDepends>,
+    #[serde(default)]
+    urgency: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TwAnnotation {
+    entry: String,
+    description: String,
+}
+
+/// Taskwarrior 2.6 and later export `depends` as a list, older versions as a comma separated
+/// string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TwDepends {
+    List(Vec<String>),
+    Joined(String),
+}
+
+impl TwDepends {
+    fn uuids(&self) -> Vec<&str> {
+        match self {
+            TwDepends::List(uuids) => uuids.iter().map(|u| u.as_str()).collect(),
+            TwDepends::Joined(uuids) => uuids.split(',').map(|u| u.trim()).collect(),
+        }
+    }
+}
+
+/// Taskwarrior timestamps look like `20231114T221320Z`.
+fn timestamp(value: &str) -> Result<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .map(|t| t.and_utc())
+        .map_err(|_| Error::Parse(format!("Invalid Taskwarrior timestamp \"{value}\"")))
+}
+
+/// `to_dump` converts the output of `task export` into a [`Dump`] that can be merged into the
+/// database.
+///
+/// Pending tasks are put on the stack in order of urgency, started ones as in progress. Waiting
+/// tasks and recurrence templates are hidden, completed and deleted tasks are completed and
+/// cancelled. The project becomes a tag, annotations become the body and `depends` becomes a
+/// `blocks` relationship. `due` and `scheduled` keep their day.
+pub(crate) fn to_dump(json: &str) -> Result<Dump> {
+    let tasks: Vec<TwTask> = serde_json::from_str(json)
+        .map_err(|e| Error::Parse(format!("Invalid Taskwarrior export: {e}")))?;
+    let ids: HashMap<&str, TaskId> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| (task.uuid.as_str(), i as TaskId + 1))
+        .collect();
+    let mut dump = Dump {
+        tasks: Vec::with_capacity(tasks.len()),
+        ..Dump::new()
+    };
+    let mut pending = Vec::new();
+    let mut tags = BTreeSet::new();
+    for task in &tasks {
+        let id = ids[task.uuid.as_str()];
+        let entry = timestamp(&task.entry)?;
+        let modified = task.modified.as_deref().map(timestamp).transpose()?;
+        let start = task.start.as_deref().map(timestamp).transpose()?;
+        let end = task.end.as_deref().map(timestamp).transpose()?;
+        // tsk dates are whole days
+        let day = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| timestamp(v).map(|t| start_of_day(local_date(t))))
+                .transpose()
+        };
+        let (status, updated) = match task.status.as_str() {
+            "pending" if start.is_some() => (TaskStatus::InProgress, start),
+            "pending" => (TaskStatus::Todo, None),
+            "completed" => (TaskStatus::Complete, end.or(modified)),
+            "deleted" => (TaskStatus::Cancelled, end.or(modified)),
+            "waiting" | "recurring" => (TaskStatus::Hidden, modified),
+            other => {
+                return Err(Error::Parse(format!(
+                    "Unknown Taskwarrior status \"{other}\" for \"{}\"",
+                    task.description
+                )))
+            }
+        };
+        let mut statuses = vec![DumpStatus {
+            status: TaskStatus::Todo,
+            updated: entry,
+        }];
+        if status != TaskStatus::Todo {
+            statuses.push(DumpStatus {
+                status,
+                updated: updated.unwrap_or(entry).max(entry),
+            });
+        }
+        if status == TaskStatus::Todo || status == TaskStatus::InProgress {
+            pending.push((task.urgency, entry, id));
+        }
+        let mut revisions = Vec::new();
+        if !task.annotations.is_empty() {
+            let mut body = Vec::with_capacity(task.annotations.len());
+            let mut updated = modified.unwrap_or(entry);
+            for annotation in &task.annotations {
+                let annotated = timestamp(&annotation.entry)?;
+                updated = updated.max(annotated);
+                body.push(format!(
+                    "{} {}",
+                    annotated.format("%Y-%m-%d"),
+                    annotation.description
+                ));
+            }
+            revisions.push(DumpRevision {
+                updated,
+                body: Some(body.join("\n")),
+                link: None,
+            });
+        }
+        let mut task_tags = task.tags.clone();
+        task_tags.extend(task.project.clone());
+        task_tags.sort();
+        task_tags.dedup();
+        tags.extend(task_tags.iter().cloned());
+        for uuid in task.depends.iter().flat_map(|d| d.uuids()) {
+            // dependencies on tasks that weren't exported are dropped
+            if let Some(&blocker) = ids.get(uuid) {
+                dump.relationships.push(DumpRelationship {
+                    left: blocker,
+                    tag: BLOCKS.to_string(),
+                    right: id,
+                });
+            }
+        }
+        dump.tasks.push(DumpTask {
+            id,
+            title: task.description.clone(),
+            created: entry,
+            statuses,
+            revisions,
+            tags: task_tags,
+            metadata: BTreeMap::new(),
+            due: day(&task.due)?,
+            scheduled: day(&task.scheduled)?,
+            ..Default::default()
+        });
+    }
+    // most urgent first, oldest first among equally urgent tasks
+    pending.sort_by(|(a, a_entry, _), (b, b_entry, _)| b.total_cmp(a).then(a_entry.cmp(b_entry)));
+    dump.stack = pending.into_iter().map(|(_, _, id)| id).collect();
+    if !dump.relationships.is_empty() {
+        tags.insert(BLOCKS.to_string());
+    }
+    dump.tags = tags.into_iter().collect();
+    dump.validate()?;
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXPORT: &str = r#"[
+        {"id":1,"description":"Write report","entry":"20231101T090000Z",
+         "modified":"20231103T100000Z","start":"20231102T080000Z","status":"pending",
+         "project":"work","tags":["writing"],"urgency":4.5,
+         "uuid":"aaaaaaaa-0000-0000-0000-000000000001",
+         "annotations":[{"entry":"20231103T100000Z","description":"ask for the numbers"}]},
+        {"id":2,"description":"Collect numbers","entry":"20231101T100000Z",
+         "modified":"20231101T100000Z","status":"pending","urgency":9.1,
+         "due":"20231110T120000Z",
+         "uuid":"aaaaaaaa-0000-0000-0000-000000000002"},
+        {"id":0,"description":"Book flights","entry":"20231020T100000Z",
+         "modified":"20231025T120000Z","end":"20231025T120000Z","status":"completed",
+         "urgency":0,"uuid":"aaaaaaaa-0000-0000-0000-000000000003"},
+        {"id":3,"description":"Renew passport","entry":"20231001T100000Z",
+         "modified":"20231001T100000Z","status":"waiting","urgency":1,
+         "uuid":"aaaaaaaa-0000-0000-0000-000000000004"},
+        {"id":4,"description":"Fix bike","entry":"20231001T100000Z","status":"pending",
+         "urgency":4.5,"depends":"aaaaaaaa-0000-0000-0000-000000000003,unknown",
+         "uuid":"aaaaaaaa-0000-0000-0000-000000000005"}
+    ]"#;
+
+    #[test]
+    fn test_to_dump() {
+        let time = |t: &str| timestamp(t).unwrap();
+        let dump = to_dump(EXPORT).unwrap();
+        assert_eq!(dump.stack, vec![2, 5, 1]);
+        assert_eq!(dump.tags, vec!["blocks", "work", "writing"]);
+        let report = &dump.tasks[0];
+        assert_eq!(report.title, "Write report");
+        assert_eq!(report.created, time("20231101T090000Z"));
+        assert_eq!(
+            report.statuses,
+            vec![
+                DumpStatus {
+                    status: TaskStatus::Todo,
+                    updated: time("20231101T090000Z"),
+                },
+                DumpStatus {
+                    status: TaskStatus::InProgress,
+                    updated: time("20231102T080000Z"),
+                },
+            ]
+        );
+        assert_eq!(
+            report.revisions,
+            vec![DumpRevision {
+                updated: time("20231103T100000Z"),
+                body: Some("2023-11-03 ask for the numbers".to_string()),
+                link: None,
+            }]
+        );
+        assert_eq!(report.tags, vec!["work", "writing"]);
+        assert_eq!(
+            dump.tasks[1].due,
+            Some(start_of_day(local_date(time("20231110T120000Z"))))
+        );
+        assert_eq!(dump.tasks[1].scheduled, None);
+        let status = |i: usize| dump.tasks[i].statuses.last().unwrap().status;
+        assert_eq!(status(1), TaskStatus::Todo);
+        assert_eq!(status(2), TaskStatus::Complete);
+        assert_eq!(status(3), TaskStatus::Hidden);
+        assert_eq!(
+            dump.relationships,
+            vec![DumpRelationship {
+                left: 3,
+                tag: BLOCKS.to_string(),
+                right: 5,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_export() {
+        assert!(to_dump("{}").is_err());
+        let bad_status = EXPORT.replace("\"waiting\"", "\"sleeping\"");
+        assert!(to_dump(&bad_status).is_err_and(|e| e.to_string().contains("sleeping")));
+        let bad_time = EXPORT.replace("20231020T100000Z", "yesterday");
+        assert!(to_dump(&bad_time).is_err_and(|e| e.to_string().contains("yesterday")));
+    }
+}
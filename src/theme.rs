@@ -0,0 +1,312 @@
+use crate::error::{Error, Result};
+use crate::types::{Tag, TaskStatus};
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const THEMES: [&str; 3] = ["dark", "light", "high-contrast"];
+
+const MODIFIERS: [(&str, Modifier); 7] = [
+    ("bold", Modifier::BOLD),
+    ("dim", Modifier::DIM),
+    ("italic", Modifier::ITALIC),
+    ("underlined", Modifier::UNDERLINED),
+    ("reversed", Modifier::REVERSED),
+    ("crossed_out", Modifier::CROSSED_OUT),
+    ("blink", Modifier::SLOW_BLINK),
+];
+
+/// Parses a style such as `"yellow"`, `"darkgray italic"` or `"black on #ffcc00 bold"`: an
+/// optional foreground color, an optional `on` background color and any number of modifiers.
+pub(crate) fn parse_style(spec: &str) -> std::result::Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        let lower = word.to_lowercase();
+        if lower == "on" {
+            let color = words
+                .next()
+                .ok_or_else(|| "expected a background color after \"on\"".to_string())?;
+            style = style.bg(Color::from_str(color)
+                .map_err(|_| format!("unknown background color \"{color}\""))?);
+        } else if let Some((_, modifier)) = MODIFIERS.iter().find(|(name, _)| *name == lower) {
+            style = style.add_modifier(*modifier);
+        } else if let (Ok(color), None) = (Color::from_str(word), style.fg) {
+            style = style.fg(color);
+        } else {
+            return Err(format!("unknown color or modifier \"{word}\""));
+        }
+    }
+    Ok(style)
+}
+
+/// Styles for everything the views draw. Status and tag styles are patched on top of `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Theme {
+    pub text: Style,
+    /// Placeholder text and secondary information such as timestamps
+    pub muted: Style,
+    pub error: Style,
+    /// Search matches in query results
+    pub highlight: Style,
+    /// The selected row of a list
+    pub selected: Style,
+    /// Lines added in a revision diff
+    pub added: Style,
+    /// Lines removed in a revision diff
+    pub removed: Style,
+    /// Tags without a style of their own
+    pub tag: Style,
+    /// Due dates that have passed
+    pub overdue: Style,
+    /// Due dates within `due_soon_days`
+    pub due_soon: Style,
+    /// The focus timer's gauge
+    pub focus: Style,
+    statuses: [Style; 5],
+    tags: HashMap<String, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+impl Theme {
+    /// The original palette, for terminals with a dark background.
+    pub(crate) fn dark() -> Theme {
+        Theme {
+            text: fg(Color::White),
+            muted: fg(Color::DarkGray),
+            error: fg(Color::Red),
+            highlight: fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            selected: Style::default().add_modifier(Modifier::REVERSED),
+            added: fg(Color::Green),
+            removed: fg(Color::Red),
+            tag: fg(Color::Cyan),
+            overdue: fg(Color::Red).add_modifier(Modifier::BOLD),
+            due_soon: fg(Color::Yellow),
+            focus: fg(Color::Green),
+            statuses: [
+                Style::default(),
+                fg(Color::Yellow),
+                fg(Color::Green),
+                fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT),
+                fg(Color::DarkGray),
+            ],
+            tags: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn light() -> Theme {
+        Theme {
+            text: fg(Color::Black),
+            muted: fg(Color::DarkGray),
+            error: fg(Color::Red),
+            highlight: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            selected: Style::default().add_modifier(Modifier::REVERSED),
+            added: fg(Color::Green),
+            removed: fg(Color::Red),
+            tag: fg(Color::Magenta),
+            overdue: fg(Color::Red).add_modifier(Modifier::BOLD),
+            due_soon: fg(Color::Blue),
+            focus: fg(Color::Green),
+            statuses: [
+                Style::default(),
+                fg(Color::Blue),
+                fg(Color::Green),
+                fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT),
+                fg(Color::DarkGray),
+            ],
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Bright colors and bold text only, avoiding grays that are hard to tell apart.
+    pub(crate) fn high_contrast() -> Theme {
+        let bold = |color| fg(color).add_modifier(Modifier::BOLD);
+        Theme {
+            text: fg(Color::White),
+            muted: fg(Color::White).add_modifier(Modifier::ITALIC),
+            error: bold(Color::LightRed),
+            highlight: bold(Color::Black).bg(Color::LightYellow),
+            selected: bold(Color::Black).bg(Color::White),
+            added: bold(Color::LightGreen),
+            removed: bold(Color::LightRed),
+            tag: bold(Color::LightCyan),
+            overdue: bold(Color::Black).bg(Color::LightRed),
+            due_soon: bold(Color::LightYellow),
+            focus: bold(Color::LightGreen),
+            statuses: [
+                Style::default(),
+                bold(Color::LightYellow),
+                bold(Color::LightGreen),
+                fg(Color::White).add_modifier(Modifier::CROSSED_OUT),
+                fg(Color::White).add_modifier(Modifier::ITALIC),
+            ],
+            tags: HashMap::new(),
+        }
+    }
+
+    /// `named` is the built-in theme called `name`.
+    pub(crate) fn named(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// `new` starts from the built-in theme `name` and applies the user's `[colors]` overrides:
+    /// `elements` keyed by UI element, `statuses` by status name and `tags` by tag name.
+    pub(crate) fn new(
+        name: &str,
+        elements: &HashMap<String, String>,
+        statuses: &HashMap<String, String>,
+        tags: &HashMap<String, String>,
+    ) -> Result<Theme> {
+        let mut theme = Theme::named(name).ok_or_else(|| {
+            Error::InvalidConfig(format!(
+                "theme: unknown theme \"{name}\", expected one of {}",
+                THEMES.join(", ")
+            ))
+        })?;
+        let style = |key: String, spec: &str| {
+            parse_style(spec).map_err(|e| Error::InvalidConfig(format!("colors.{key}: {e}")))
+        };
+        for (element, spec) in elements {
+            let field = match element.as_str() {
+                "text" => &mut theme.text,
+                "muted" => &mut theme.muted,
+                "error" => &mut theme.error,
+                "highlight" => &mut theme.highlight,
+                "selected" => &mut theme.selected,
+                "added" => &mut theme.added,
+                "removed" => &mut theme.removed,
+                "tag" => &mut theme.tag,
+                "overdue" => &mut theme.overdue,
+                "due_soon" => &mut theme.due_soon,
+                "focus" => &mut theme.focus,
+                _ => {
+                    return Err(Error::InvalidConfig(format!(
+                        "colors.{element}: unknown element, expected one of text, muted, error, \
+                         highlight, selected, added, removed, tag, overdue, due_soon, focus, \
+                         status or tags"
+                    )))
+                }
+            };
+            *field = style(element.clone(), spec)?;
+        }
+        for (status, spec) in statuses {
+            let parsed = TaskStatus::from_str(status).map_err(|_| {
+                Error::InvalidConfig(format!("colors.status.{status}: unknown status"))
+            })?;
+            theme.statuses[parsed as usize] = style(format!("status.{status}"), spec)?;
+        }
+        for (tag, spec) in tags {
+            let style = style(format!("tags.{tag}"), spec)?;
+            theme
+                .tags
+                .insert(tag.trim_start_matches('#').to_lowercase(), style);
+        }
+        Ok(theme)
+    }
+
+    /// `status` is the style for tasks with `status`, on top of `text`.
+    pub(crate) fn status(&self, status: TaskStatus) -> Style {
+        self.text.patch(self.statuses[status as usize])
+    }
+
+    /// `tag_style` is the style configured for `tag`, or the default tag style.
+    pub(crate) fn tag_style(&self, tag: &Tag) -> Style {
+        self.text.patch(
+            *self
+                .tags
+                .get(&tag.as_str().to_lowercase())
+                .unwrap_or(&self.tag),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error(
+        name: &str,
+        elements: &[(&str, &str)],
+        statuses: &[(&str, &str)],
+        tags: &[(&str, &str)],
+    ) -> String {
+        let map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        match Theme::new(name, &map(elements), &map(statuses), &map(tags)) {
+            Err(Error::InvalidConfig(e)) => e,
+            other => panic!("expected a config error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(parse_style("yellow"), Ok(fg(Color::Yellow)));
+        assert_eq!(parse_style(""), Ok(Style::default()));
+        assert_eq!(
+            parse_style("black on #ffcc00 bold"),
+            Ok(fg(Color::Black)
+                .bg(Color::Rgb(0xff, 0xcc, 0x00))
+                .add_modifier(Modifier::BOLD))
+        );
+        assert_eq!(
+            parse_style("Italic dark-gray"),
+            Ok(fg(Color::DarkGray).add_modifier(Modifier::ITALIC))
+        );
+        assert!(parse_style("red blue").is_err());
+        assert!(parse_style("red on").is_err());
+        assert!(parse_style("sparkly").is_err());
+    }
+
+    #[test]
+    fn test_theme_overrides() {
+        let theme = Theme::new(
+            "light",
+            &HashMap::from([("muted".to_string(), "gray".to_string())]),
+            &HashMap::from([("done".to_string(), "green bold".to_string())]),
+            &HashMap::from([("#Urgent".to_string(), "red".to_string())]),
+        )
+        .unwrap();
+        assert_eq!(theme.muted, fg(Color::Gray));
+        assert_eq!(theme.error, Theme::light().error);
+        assert_eq!(
+            theme.status(TaskStatus::Complete),
+            fg(Color::Green).add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.status(TaskStatus::Todo), fg(Color::Black));
+        assert_eq!(
+            theme.tag_style(&Tag::from("urgent".to_string())),
+            fg(Color::Red)
+        );
+        assert_eq!(
+            theme.tag_style(&Tag::from("later".to_string())),
+            fg(Color::Magenta)
+        );
+    }
+
+    #[test]
+    fn test_invalid_theme() {
+        assert!(error("solarized", &[], &[], &[]).contains("unknown theme"));
+        assert!(error("dark", &[("border", "red")], &[], &[]).contains("colors.border"));
+        assert!(error("dark", &[("text", "blurple")], &[], &[]).contains("colors.text"));
+        assert!(error("dark", &[], &[("later", "red")], &[]).contains("colors.status.later"));
+        assert!(error("dark", &[], &[], &[("x", "red on")]).contains("colors.tags.x"));
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    db::Db,
+    error::Result,
+    types::{local_date, start_of_day, TaskId, WorkInterval},
+};
+use chrono::{DateTime, Days, Duration, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+/// What the rows of a report are grouped by.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Group {
+    Day,
+    Tag,
+    Task,
+}
+
+/// Shown in place of a tag for untagged tasks.
+const UNTAGGED: &str = "-";
+
+/// One row of a report. Only the columns the report is grouped by are set.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Row {
+    pub day: Option<NaiveDate>,
+    pub tag: Option<String>,
+    pub task: Option<(TaskId, String)>,
+    pub time: Duration,
+}
+
+/// The day, tag and task a row is for, as far as the report is grouped by them.
+type Key = (Option<NaiveDate>, Option<String>, Option<(TaskId, String)>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Report {
+    pub by: Vec<Group>,
+    pub rows: Vec<Row>,
+    /// Every tracked second counted once, even when a task with several tags shows up in a row
+    /// for each of them
+    pub total: Duration,
+}
+
+/// `split_days` splits `work` at local midnight, so each piece falls on a single day. Manual
+/// entries count on the day they were logged for.
+fn split_days(work: &WorkInterval) -> Vec<(NaiveDate, Duration)> {
+    let mut out = Vec::new();
+    let mut started = work.started;
+    let mut day = local_date(started);
+    let end = match work.started.checked_add_signed(work.duration) {
+        Some(end) if !work.manual && work.duration > Duration::zero() => end,
+        _ => return vec![(day, work.duration)],
+    };
+    while started < end {
+        let midnight = start_of_day(day + Days::new(1));
+        let until = end.min(midnight);
+        out.push((day, until - started));
+        started = until;
+        day = day + Days::new(1);
+    }
+    out
+}
+
+/// `report` adds up the time spent on tasks between the days `from` and `to`, both included,
+/// grouped by `by`.
+pub(crate) fn report(
+    db: &Db,
+    by: &[Group],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    now: DateTime<Utc>,
+) -> Result<Report> {
+    let grouped = |group| by.contains(&group);
+    let mut tasks: HashMap<TaskId, (String, Vec<String>)> = HashMap::new();
+    let mut rows: BTreeMap<Key, Duration> = BTreeMap::new();
+    let mut total = Duration::zero();
+    for work in db.get_work(None, now)? {
+        let (title, tags) = match tasks.entry(work.task_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let task = db.get_task(work.task_id)?;
+                let tags = task.tags.iter().map(|t| t.as_str().to_string()).collect();
+                entry.insert((task.title, tags))
+            }
+        };
+        for (day, time) in split_days(&work) {
+            if from.is_some_and(|from| day < from) || to.is_some_and(|to| day > to) {
+                continue;
+            }
+            total = total + time;
+            let tags = match (grouped(Group::Tag), tags.is_empty()) {
+                (false, _) => vec![None],
+                (true, true) => vec![Some(UNTAGGED.to_string())],
+                (true, false) => tags.iter().cloned().map(Some).collect(),
+            };
+            for tag in tags {
+                let key = (
+                    Some(day).filter(|_| grouped(Group::Day)),
+                    tag,
+                    Some((work.task_id, title.clone())).filter(|_| grouped(Group::Task)),
+                );
+                let sum = rows.entry(key).or_insert_with(Duration::zero);
+                *sum = *sum + time;
+            }
+        }
+    }
+    Ok(Report {
+        by: by.to_vec(),
+        rows: rows
+            .into_iter()
+            .map(|((day, tag, task), time)| Row {
+                day,
+                tag,
+                task,
+                time,
+            })
+            .collect(),
+        total,
+    })
+}
+
+/// `hours` formats `time` as hours and minutes, e.g. `26:05`, which unlike days adds up on an
+/// invoice.
+pub(crate) fn hours(time: Duration) -> String {
+    let minutes = time.num_minutes();
+    let sign = if minutes < 0 { "-" } else { "" };
+    format!("{sign}{}:{:02}", minutes.abs() / 60, minutes.abs() % 60)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Row {
+    /// The grouped columns, in the order of the report's header.
+    fn columns(&self) -> Vec<String> {
+        let mut columns = Vec::new();
+        columns.extend(self.day.map(|d| d.to_string()));
+        columns.extend(self.tag.clone());
+        if let Some((task_id, title)) = &self.task {
+            columns.push(format!("TSK-{task_id}"));
+            columns.push(title.clone());
+        }
+        columns
+    }
+}
+
+impl Report {
+    fn header(&self) -> Vec<&'static str> {
+        let mut header = Vec::new();
+        for group in [Group::Day, Group::Tag, Group::Task] {
+            if self.by.contains(&group) {
+                header.extend(match group {
+                    Group::Day => &["Day"][..],
+                    Group::Tag => &["Tag"][..],
+                    Group::Task => &["Task", "Title"][..],
+                });
+            }
+        }
+        header
+    }
+
+    /// `to_text` lays the report out as a table, time in hours and minutes.
+    pub(crate) fn to_text(&self) -> String {
+        let mut table = vec![self
+            .header()
+            .into_iter()
+            .map(String::from)
+            .chain(["Time".to_string()])
+            .collect::<Vec<_>>()];
+        for row in &self.rows {
+            let mut columns = row.columns();
+            columns.push(hours(row.time));
+            table.push(columns);
+        }
+        let mut total = vec![String::new(); table[0].len()];
+        total[0] = "Total".to_string();
+        *total.last_mut().unwrap() = hours(self.total);
+        table.push(total);
+        let widths = (0..table[0].len())
+            .map(|i| table.iter().map(|r| r[i].chars().count()).max().unwrap())
+            .collect::<Vec<_>>();
+        let mut out = String::new();
+        for row in &table {
+            let last = row.len() - 1;
+            let mut line = String::new();
+            for (i, column) in row.iter().enumerate() {
+                if i == last {
+                    // times are right aligned
+                    line.push_str(&format!("{column:>width$}", width = widths[i]));
+                } else {
+                    line.push_str(&format!("{column:width$}  ", width = widths[i]));
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// `to_csv` writes a row per group with the time in seconds and decimal hours, for
+    /// spreadsheets and billing tools.
+    pub(crate) fn to_csv(&self) -> String {
+        let mut header = self.header();
+        header.extend(["Seconds", "Hours"]);
+        let mut out = header.join(",").to_lowercase();
+        out.push('\n');
+        for row in &self.rows {
+            let mut columns = row
+                .columns()
+                .iter()
+                .map(|c| csv_field(c))
+                .collect::<Vec<_>>();
+            columns.push(row.time.num_seconds().to_string());
+            columns.push(format!("{:.2}", row.time.num_seconds() as f64 / 3600.0));
+            out.push_str(&columns.join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "rows": self.rows.iter().map(|row| {
+                let mut value = json!({ "seconds": row.time.num_seconds() });
+                if let Some(day) = row.day {
+                    value["day"] = json!(day.to_string());
+                }
+                if let Some(tag) = &row.tag {
+                    value["tag"] = json!(tag);
+                }
+                if let Some((task_id, title)) = &row.task {
+                    value["task"] = json!(task_id);
+                    value["title"] = json!(title);
+                }
+                value
+            }).collect::<Vec<_>>(),
+            "total_seconds": self.total.num_seconds(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_split_days() {
+        let started = start_of_day(date("2026-10-15")) + Duration::hours(22);
+        let work = WorkInterval {
+            task_id: 1,
+            started,
+            duration: Duration::hours(27),
+            manual: false,
+        };
+        assert_eq!(
+            split_days(&work),
+            vec![
+                (date("2026-10-15"), Duration::hours(2)),
+                (date("2026-10-16"), Duration::hours(24)),
+                (date("2026-10-17"), Duration::hours(1)),
+            ]
+        );
+        let correction = WorkInterval {
+            duration: Duration::minutes(-15),
+            manual: true,
+            ..work
+        };
+        assert_eq!(
+            split_days(&correction),
+            vec![(date("2026-10-15"), Duration::minutes(-15))]
+        );
+        let endless = WorkInterval {
+            duration: Duration::max_value(),
+            ..work
+        };
+        assert_eq!(
+            split_days(&endless),
+            vec![(date("2026-10-15"), Duration::max_value())]
+        );
+    }
+
+    #[test]
+    fn test_report() -> Result<()> {
+        let mut db = Db::in_memory()?;
+        let report_task = db.push_task("Write report, draft".to_string())?;
+        let now = Utc::now();
+        let day = local_date(now);
+        db.log_time(report_task, start_of_day(day), Duration::minutes(90))?;
+        db.log_time(
+            report_task,
+            start_of_day(date("2026-01-05")),
+            Duration::hours(2),
+        )?;
+        db.log_time(report_task, start_of_day(day), Duration::minutes(-30))?;
+
+        let by_task = report(&db, &[Group::Task], None, None, now)?;
+        assert_eq!(by_task.total, Duration::hours(3));
+        assert_eq!(
+            by_task.rows,
+            vec![Row {
+                day: None,
+                tag: None,
+                task: Some((report_task, "Write report, draft".to_string())),
+                time: Duration::hours(3),
+            }]
+        );
+        let today = report(&db, &[Group::Day, Group::Task], Some(day), Some(day), now)?;
+        assert_eq!(today.total, Duration::hours(1));
+        assert_eq!(
+            today.to_text(),
+            format!(
+                "Day         Task   Title                Time\n\
+                 {day}  TSK-1  Write report, draft  1:00\n\
+                 Total                                   1:00\n"
+            )
+        );
+        assert_eq!(
+            today.to_csv(),
+            format!(
+                "day,task,title,seconds,hours\n{day},TSK-1,\"Write report, draft\",3600,1.00\n"
+            )
+        );
+        assert_eq!(today.to_json()["rows"][0]["task"], 1);
+        assert_eq!(today.to_json()["total_seconds"], 3600);
+
+        let by_tag = report(&db, &[Group::Tag], None, None, now)?;
+        assert_eq!(by_tag.rows[0].tag.as_deref(), Some(UNTAGGED));
+
+        // Time logged on a task whose push is undone goes with it, and comes back on redo
+        let oops = db.push_task("oops".to_string())?;
+        db.log_time(oops, now, Duration::minutes(30))?;
+        let logged = report(&db, &[Group::Task], None, None, now)?;
+        db.undo()?;
+        assert_eq!(report(&db, &[Group::Task], None, None, now)?, by_task);
+        db.redo()?;
+        assert_eq!(report(&db, &[Group::Task], None, None, now)?, logged);
+        assert_eq!(logged.total, Duration::hours(3) + Duration::minutes(30));
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{
+    dump::{Dump, DumpStatus, DumpTask},
+    error::Result,
+    types::{start_of_day, TaskId, TaskStatus, DATE_FORMAT},
+};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// The `due:` and threshold `t:` keys, by convention holding the due and scheduled dates.
+const DUE: &str = "due";
+const THRESHOLD: &str = "t";
+
+/// One line of a todo.txt file, e.g. `x 2023-11-03 2023-11-01 Book flights +travel`.
+#[derive(Debug, PartialEq, Eq)]
+struct Line<'a> {
+    done: bool,
+    priority: Option<char>,
+    completed: Option<NaiveDate>,
+    created: Option<NaiveDate>,
+    description: &'a str,
+}
+
+/// `split_date` splits a leading `YYYY-MM-DD ` off `text`.
+fn split_date(text: &str) -> (Option<NaiveDate>, &str) {
+    let (word, rest) = text.split_once(' ').unwrap_or((text, ""));
+    match NaiveDate::parse_from_str(word, DATE_FORMAT) {
+        Ok(date) => (Some(date), rest.trim_start()),
+        Err(_) => (None, text),
+    }
+}
+
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    let mut rest = line.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let done = rest.starts_with("x ");
+    if done {
+        rest = rest[2..].trim_start();
+    }
+    let mut priority = None;
+    if let [b'(', p @ b'A'..=b'Z', b')', b' ', ..] = rest.as_bytes() {
+        priority = Some(*p as char);
+        rest = rest[4..].trim_start();
+    }
+    let (first, rest) = split_date(rest);
+    let (completed, created, rest) = if done {
+        // the creation date is optional, a single date is when the task was completed
+        let (second, rest) = split_date(rest);
+        (first, second.or(first), rest)
+    } else {
+        (None, first, rest)
+    };
+    Some(Line {
+        done,
+        priority,
+        completed,
+        created,
+        description: rest,
+    })
+}
+
+/// `is_date_pair` is whether `word` is a `due:` or `t:` pair holding a date. Those are kept as
+/// the task's due and scheduled dates rather than in its title and metadata, so the dates can
+/// be changed or cleared in tsk.
+fn is_date_pair(word: &str) -> bool {
+    word.split_once(':').is_some_and(|(key, value)| {
+        (key == DUE || key == THRESHOLD) && NaiveDate::parse_from_str(value, DATE_FORMAT).is_ok()
+    })
+}
+
+/// `without_date_pairs` drops the `due:` and `t:` dates from `description`, leaving it as it was
+/// when there are none.
+fn without_date_pairs(description: &str) -> String {
+    if description.split_whitespace().any(is_date_pair) {
+        description
+            .split_whitespace()
+            .filter(|word| !is_date_pair(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        description.to_string()
+    }
+}
+
+/// `tags_and_metadata` finds the `+project` and `@context` tags and the `key:value` pairs in a
+/// description.
+fn tags_and_metadata(description: &str) -> (Vec<String>, BTreeMap<String, String>) {
+    let mut tags = Vec::new();
+    let mut metadata = BTreeMap::new();
+    for word in description.split_whitespace() {
+        if let Some(tag) = word.strip_prefix(['+', '@']).filter(|t| !t.is_empty()) {
+            tags.push(tag.to_string());
+        } else if let Some((key, value)) = word.split_once(':') {
+            // skip URLs such as https://example.com
+            if !key.is_empty() && !value.is_empty() && !value.starts_with("//") {
+                metadata.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    tags.sort();
+    tags.dedup();
+    (tags, metadata)
+}
+
+/// `to_dump` converts a todo.txt file into a [`Dump`] that can be merged into the database.
+///
+/// The description is kept as the title, so a task is written back exactly as it was read.
+/// `+project` and `@context` become tags and `key:value` pairs become metadata, except that
+/// `due:` and the `t:` threshold date set the due and scheduled dates instead. Open tasks are
+/// put on the stack by priority, then in the order they appear, and tasks marked `x` are
+/// completed.
+pub(crate) fn to_dump(text: &str) -> Result<Dump> {
+    let now = Utc::now();
+    let mut dump = Dump::new();
+    let mut tags = BTreeSet::new();
+    let mut open = Vec::new();
+    for line in text.lines().filter_map(parse_line) {
+        let id = dump.tasks.len() as TaskId + 1;
+        let created = line.created.map(start_of_day).unwrap_or(now);
+        let mut statuses = vec![DumpStatus {
+            status: TaskStatus::Todo,
+            updated: created,
+        }];
+        if line.done {
+            statuses.push(DumpStatus {
+                status: TaskStatus::Complete,
+                updated: line.completed.map(start_of_day).unwrap_or(now).max(created),
+            });
+        } else {
+            open.push((line.priority, id));
+        }
+        let (task_tags, mut metadata) = tags_and_metadata(line.description);
+        tags.extend(task_tags.iter().cloned());
+        let day = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(|v| NaiveDate::parse_from_str(v, DATE_FORMAT).ok())
+                .map(start_of_day)
+        };
+        let (due, scheduled) = (day(DUE), day(THRESHOLD));
+        if due.is_some() {
+            metadata.remove(DUE);
+        }
+        if scheduled.is_some() {
+            metadata.remove(THRESHOLD);
+        }
+        dump.tasks.push(DumpTask {
+            id,
+            title: without_date_pairs(line.description),
+            created,
+            statuses,
+            revisions: Vec::new(),
+            tags: task_tags,
+            metadata,
+            due,
+            scheduled,
+            ..Default::default()
+        });
+    }
+    // (A) first, tasks without a priority last
+    open.sort_by_key(|(priority, id)| (priority.is_none(), *priority, *id));
+    dump.stack = open.into_iter().map(|(_, id)| id).collect();
+    dump.tags = tags.into_iter().collect();
+    dump.validate()?;
+    Ok(dump)
+}
+
+/// `from_dump` writes `dump` as a todo.txt file. The stack comes first, its top 25 tasks with
+/// priorities `(A)` to `(Y)` and the rest as `(Z)`, so reading the file back restores the order.
+/// Tasks off the stack follow without a priority, complete and cancelled ones marked `x`.
+///
+/// Bodies, links and relationships have no place in todo.txt and are left out. Tags and
+/// metadata missing from the title are appended to it, and the due and scheduled dates replace
+/// any `due:` and `t:` dates left in the title or metadata.
+pub(crate) fn from_dump(dump: &Dump) -> String {
+    let tasks: HashMap<TaskId, &DumpTask> = dump.tasks.iter().map(|t| (t.id, t)).collect();
+    let stacked: HashSet<TaskId> = dump.stack.iter().copied().collect();
+    let mut out = String::new();
+    for (position, task_id) in dump.stack.iter().enumerate() {
+        let priority = (b'A' + position.min(25) as u8) as char;
+        out.push_str(&write_line(tasks[task_id], Some(priority)));
+        out.push('\n');
+    }
+    for task in dump.tasks.iter().filter(|t| !stacked.contains(&t.id)) {
+        out.push_str(&write_line(task, None));
+        out.push('\n');
+    }
+    out
+}
+
+fn write_line(task: &DumpTask, priority: Option<char>) -> String {
+    let date = |time: DateTime<Utc>| time.with_timezone(&Local).format(DATE_FORMAT).to_string();
+    let latest = task.statuses.iter().max_by_key(|s| s.updated);
+    let mut parts = Vec::new();
+    match latest {
+        Some(s) if matches!(s.status, TaskStatus::Complete | TaskStatus::Cancelled) => {
+            parts.push("x".to_string());
+            parts.push(date(s.updated));
+        }
+        _ => parts.extend(priority.map(|p| format!("({p})"))),
+    }
+    parts.push(date(task.created));
+    let title = without_date_pairs(&task.title);
+    parts.push(title.clone());
+    let words: HashSet<&str> = title.split_whitespace().collect();
+    for tag in &task.tags {
+        if !words.contains(format!("+{tag}").as_str())
+            && !words.contains(format!("@{tag}").as_str())
+        {
+            parts.push(format!("+{tag}"));
+        }
+    }
+    let mut metadata = task.metadata.clone();
+    metadata.retain(|key, value| !is_date_pair(&format!("{key}:{value}")));
+    for (key, day) in [(DUE, task.due), (THRESHOLD, task.scheduled)] {
+        if let Some(day) = day {
+            metadata.insert(key.to_string(), date(day));
+        }
+    }
+    for (key, value) in &metadata {
+        let pair = format!("{key}:{value}");
+        if !words.contains(pair.as_str()) {
+            parts.push(pair);
+        }
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TODO: &str = "(A) 2023-11-01 Call mom +family @phone due:2023-11-05
+(B) 2023-11-02 Write report +work
+2023-10-30 Water plants
+x 2023-11-03 2023-11-01 Book flights +travel
+";
+
+    #[test]
+    fn test_parse_line() {
+        let date = |d: &str| NaiveDate::parse_from_str(d, DATE_FORMAT).ok();
+        assert_eq!(
+            parse_line("x 2023-11-03 2023-11-01 Book flights"),
+            Some(Line {
+                done: true,
+                priority: None,
+                completed: date("2023-11-03"),
+                created: date("2023-11-01"),
+                description: "Book flights",
+            })
+        );
+        assert_eq!(
+            parse_line("x 2023-11-03 Book flights").unwrap().created,
+            date("2023-11-03")
+        );
+        assert_eq!(
+            parse_line("(C) Call the xenon supplier"),
+            Some(Line {
+                done: false,
+                priority: Some('C'),
+                completed: None,
+                created: None,
+                description: "Call the xenon supplier",
+            })
+        );
+        // only an uppercase letter followed by a space is a priority
+        assert_eq!(parse_line("(a) lower").unwrap().priority, None);
+        assert!(!parse_line("xylophone lessons").unwrap().done);
+        assert_eq!(parse_line("   "), None);
+    }
+
+    #[test]
+    fn test_tags_and_metadata() {
+        let (tags, metadata) =
+            tags_and_metadata("Call +family @phone due:2023-11-05 at https://example.com + a:");
+        assert_eq!(tags, vec!["family", "phone"]);
+        assert_eq!(
+            metadata,
+            BTreeMap::from([("due".to_string(), "2023-11-05".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_to_dump() {
+        let dump = to_dump(&format!("2023-11-05 Unprioritized\n{TODO}")).unwrap();
+        assert_eq!(dump.stack, vec![2, 3, 1, 4]);
+        assert_eq!(dump.tags, vec!["family", "phone", "travel", "work"]);
+        let call = &dump.tasks[1];
+        assert_eq!(call.title, "Call mom +family @phone");
+        assert_eq!(
+            call.created,
+            start_of_day(NaiveDate::from_ymd_opt(2023, 11, 1).unwrap())
+        );
+        assert!(call.metadata.is_empty());
+        assert_eq!(
+            call.due,
+            Some(start_of_day(NaiveDate::from_ymd_opt(2023, 11, 5).unwrap()))
+        );
+        assert_eq!(call.scheduled, None);
+        let flights = &dump.tasks[4];
+        assert_eq!(flights.statuses.len(), 2);
+        assert_eq!(flights.statuses[1].status, TaskStatus::Complete);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(
+            from_dump(&to_dump(TODO).unwrap()),
+            TODO.replace("2023-10-30", "(C) 2023-10-30")
+        );
+        let mut dump = to_dump(TODO).unwrap();
+        dump.tasks[1].tags.push("urgent".to_string());
+        dump.tasks[1]
+            .metadata
+            .insert("id".to_string(), "7".to_string());
+        dump.tasks[1].scheduled = Some(dump.tasks[1].created);
+        dump.tasks[2].statuses[0].status = TaskStatus::Cancelled;
+        dump.stack.retain(|id| *id != 3);
+        assert_eq!(
+            from_dump(&dump).lines().collect::<Vec<_>>(),
+            vec![
+                "(A) 2023-11-01 Call mom +family @phone due:2023-11-05",
+                "(B) 2023-11-02 Write report +work +urgent id:7 t:2023-11-02",
+                "x 2023-10-30 2023-10-30 Water plants",
+                "x 2023-11-03 2023-11-01 Book flights +travel",
+            ]
+        );
+
+        // the dates are written from the task, not from what was imported
+        let mut dump = to_dump("2023-11-01 Call mom due:2023-11-05 t:soon").unwrap();
+        assert_eq!(dump.tasks[0].metadata["t"], "soon");
+        dump.tasks[0].due = None;
+        assert_eq!(
+            from_dump(&dump).lines().next(),
+            Some("(A) 2023-11-01 Call mom t:soon")
+        );
+        dump.tasks[0].due = Some(start_of_day(NaiveDate::from_ymd_opt(2023, 11, 7).unwrap()));
+        // titles and metadata imported before dates were split off still hold them
+        dump.tasks[0].title = "Call mom due:2023-11-05".to_string();
+        dump.tasks[0]
+            .metadata
+            .insert("due".to_string(), "2023-11-05".to_string());
+        assert_eq!(
+            from_dump(&dump).lines().next(),
+            Some("(A) 2023-11-01 Call mom due:2023-11-07 t:soon")
+        );
+    }
+}
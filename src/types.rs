@@ -1,12 +1,16 @@
 #![allow(dead_code)]
-use std::{fmt::Display, mem::replace};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
-use crate::error::Error;
-use chrono::{DateTime, Utc};
+use crate::{error::Error, recurrence::Recurrence};
+use chrono::{DateTime, Days, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
 use uris::Uri;
 
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub(crate) enum TaskStatus {
+    #[default]
     Todo = 0,
     InProgress = 1,
     Complete = 2,
@@ -27,6 +31,34 @@ impl Display for TaskStatus {
     }
 }
 
+impl TaskStatus {
+    /// The name of the status as it is written in queries.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            TaskStatus::Todo => "todo",
+            TaskStatus::InProgress => "inprogress",
+            TaskStatus::Complete => "complete",
+            TaskStatus::Cancelled => "cancelled",
+            TaskStatus::Hidden => "hidden",
+        }
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "todo" => Self::Todo,
+            "inprogress" | "in-progress" | "started" => Self::InProgress,
+            "complete" | "completed" | "done" => Self::Complete,
+            "cancelled" | "canceled" => Self::Cancelled,
+            "hidden" => Self::Hidden,
+            _ => return Err(Error::Parse(format!("Unknown task status `{s}`"))),
+        })
+    }
+}
+
 impl TryFrom<u8> for TaskStatus {
     type Error = Error;
 
@@ -46,15 +78,28 @@ impl TryFrom<u8> for TaskStatus {
     }
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        Self::Todo
+pub(crate) type TaskId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Tag(String);
+
+impl Tag {
+    pub(crate) fn as_str(&self) -> &str {
+        self.0.as_str()
     }
 }
 
-pub(crate) type TaskId = u64;
+impl From<String> for Tag {
+    fn from(value: String) -> Self {
+        Tag(value)
+    }
+}
 
-pub(crate) struct Tag(String);
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
 
 #[derive(Default)]
 pub(crate) struct Task {
@@ -63,6 +108,14 @@ pub(crate) struct Task {
     pub(crate) status: TaskStatus,
     pub(crate) created: DateTime<Utc>,
     pub(crate) content: Option<TaskContent>,
+    pub(crate) tags: Vec<Tag>,
+    pub(crate) relationships: Vec<Relationship>,
+    pub(crate) metadata: BTreeMap<String, String>,
+    /// The start of the day the task is due
+    pub(crate) due: Option<DateTime<Utc>>,
+    /// The start of the day work on the task is planned to start
+    pub(crate) scheduled: Option<DateTime<Utc>>,
+    pub(crate) recurrence: Option<Recurrence>,
 }
 
 pub(crate) struct TaskContent {
@@ -78,27 +131,194 @@ impl Task {
             title,
             created,
             content: None,
+            tags: Vec::new(),
+            relationships: Vec::new(),
+            metadata: BTreeMap::new(),
+            due: None,
+            scheduled: None,
+            recurrence: None,
         }
     }
 
     pub(crate) fn set_content(&mut self, content: TaskContent) -> Option<TaskContent> {
-        replace(&mut self.content, Some(content))
+        self.content.replace(content)
     }
 }
 
+/// Which side of a relationship the given task sits on. A task `X` matches
+/// `Relation("blocks", Right(12))` when `X blocks tsk-12`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum RelationshipSide {
     Left(TaskId),
     Right(TaskId),
 }
 
+/// How the backlog is ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BacklogOrder {
+    Created,
+    Status,
+}
+
+/// `Revision` is one version of a task's content, as stored in TASK_CONTENT.
+pub(crate) struct Revision {
+    pub(crate) updated: DateTime<Utc>,
+    pub(crate) content: TaskContent,
+}
+
+/// `StatusChange` is one transition in a task's status history. `until` is when the following
+/// transition happened, or `None` if this is the current status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StatusChange {
+    pub(crate) status: TaskStatus,
+    pub(crate) updated: DateTime<Utc>,
+    pub(crate) until: Option<DateTime<Utc>>,
+}
+
+impl StatusChange {
+    /// How long the task spent in this status, counting up to `now` for the current status.
+    pub(crate) fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.until.unwrap_or(now) - self.updated
+    }
+}
+
+/// The most time a single manual entry adds or takes off, in hours. Anything longer is a typo.
+pub(crate) const MAX_TIME_ENTRY_HOURS: i64 = 1000;
+
+/// `WorkInterval` is time spent on a task: a stretch in progress, or a manual entry, which may be
+/// negative to correct the tracked time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WorkInterval {
+    pub(crate) task_id: TaskId,
+    pub(crate) started: DateTime<Utc>,
+    pub(crate) duration: Duration,
+    pub(crate) manual: bool,
+}
+
+/// `SearchResult` is a task matched by a query, with a snippet of the text that matched when the
+/// query searched text.
+pub(crate) struct SearchResult {
+    pub(crate) task: Task,
+    pub(crate) snippet: Option<String>,
+}
+
+/// `Relationship` is one RELATIONSHIP row as seen from a task, `side` holding the other task.
+/// For `tsk-1 blocks tsk-2`, TSK-1 sees `Right(2)` and TSK-2 sees `Left(1)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Relationship {
+    pub(crate) tag: String,
+    pub(crate) side: RelationshipSide,
+    /// The title of the other task
+    pub(crate) title: String,
+}
+
+/// Due and scheduled dates are days, written like this and stored as the start of the day in
+/// local time.
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// `start_of_day` is midnight at the start of `date`, local time.
+pub(crate) fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// `local_date` is the day `time` falls on, local time.
+pub(crate) fn local_date(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
+/// A day as written in commands and queries: `yesterday`, `today`, `tomorrow` or `2026-11-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Day {
+    Yesterday,
+    Today,
+    Tomorrow,
+    On(NaiveDate),
+}
+
+impl Day {
+    pub(crate) fn date(&self) -> NaiveDate {
+        let today = Local::now().date_naive();
+        match self {
+            Day::Yesterday => today - Days::new(1),
+            Day::Today => today,
+            Day::Tomorrow => today + Days::new(1),
+            Day::On(date) => *date,
+        }
+    }
+}
+
+impl Display for Day {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Day::Yesterday => write!(f, "yesterday"),
+            Day::Today => write!(f, "today"),
+            Day::Tomorrow => write!(f, "tomorrow"),
+            Day::On(date) => write!(f, "{}", date.format(DATE_FORMAT)),
+        }
+    }
+}
+
+/// Which due dates a `due:` query matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DueFilter {
+    /// `due:any`, any task with a due date
+    Any,
+    /// `due:overdue`, tasks due before today
+    Overdue,
+    /// `due:2026-11-01`, tasks due on or before the day
+    By(Day),
+}
+
+impl Display for DueFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DueFilter::Any => write!(f, "any"),
+            DueFilter::Overdue => write!(f, "overdue"),
+            DueFilter::By(day) => write!(f, "{day}"),
+        }
+    }
+}
+
 /// `Query` represents a segment of a query when entering "query mode".
+///
+/// The `bool` on `Tag`, `Status` and `Due` negates the filter when `true`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum QueryArgs {
-    /// Query tasks with a given tag
+    /// Query tasks with (or without) a given tag
     Tag(bool, Tag),
-    /// Query tasks of a certain status
+    /// Query tasks of (or not of) a certain status
     Status(bool, TaskStatus),
     /// Query tasks usint FTS
     Text(String),
     /// Query tasks with a certain relationship
     Relation(String, RelationshipSide),
+    /// Query tasks by due date
+    Due(bool, DueFilter),
+}
+
+impl Display for QueryArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryArgs::Tag(neg, tag) => write!(f, "{}{}", if *neg { '-' } else { '+' }, tag.0),
+            QueryArgs::Status(neg, status) => {
+                write!(f, "{}status:{}", if *neg { "-" } else { "" }, status.name())
+            }
+            QueryArgs::Text(t) if t.contains(char::is_whitespace) => write!(f, "\"{t}\""),
+            QueryArgs::Text(t) => write!(f, "{t}"),
+            QueryArgs::Relation(rel, RelationshipSide::Left(task_id)) => {
+                write!(f, "tsk-{task_id}:{rel}")
+            }
+            QueryArgs::Relation(rel, RelationshipSide::Right(task_id)) => {
+                write!(f, "{rel}:tsk-{task_id}")
+            }
+            QueryArgs::Due(neg, filter) => {
+                write!(f, "{}due:{filter}", if *neg { "-" } else { "" })
+            }
+        }
+    }
 }
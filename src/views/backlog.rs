@@ -0,0 +1,166 @@
+use chrono::Local;
+use ratatui::{
+    backend::Backend,
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Terminal,
+};
+use tui_textarea::{Input, Key, TextArea};
+
+use crate::{
+    commands::{parse_backlog_command, BacklogCommand, Command, TaskOrRelative},
+    config::Config,
+    db::Db,
+    error::Result,
+    keymap::Action,
+    types::{BacklogOrder, Task, TaskId},
+};
+
+use super::{home::AppState, reset_placeholder, show_error};
+
+/// Resolves `task` against the backlog, falling back to the selected task.
+fn resolve_task(
+    backlog: &[Task],
+    selected: Option<usize>,
+    task: Option<&TaskOrRelative>,
+) -> Option<TaskId> {
+    match task {
+        Some(TaskOrRelative::Task(task_id)) => Some(*task_id),
+        Some(TaskOrRelative::Relative(i)) => backlog.get(*i as usize).map(|t| t.id),
+        None => selected.and_then(|i| backlog.get(i)).map(|t| t.id),
+    }
+}
+
+/// `render_backlog` lists every task that isn't on the stack. Up and Down select a task, `push`
+/// puts it back on the stack and `sort` reorders the list. Esc returns to the home view.
+pub(crate) fn render_backlog<B: Backend>(
+    term: &mut Terminal<B>,
+    db: &mut Db,
+    config: &Config,
+) -> Result<AppState> {
+    let layout = Layout::default()
+        .constraints([Constraint::Min(1), Constraint::Length(3)])
+        .direction(Direction::Vertical);
+    let mut command_editor = TextArea::default();
+    command_editor.set_cursor_line_style(Style::default());
+    command_editor.set_block(Block::default().borders(Borders::ALL));
+    command_editor.set_style(config.theme.text);
+    reset_placeholder(&mut command_editor, &config.theme);
+    let (mut order, mut descending) = (BacklogOrder::Created, true);
+    let mut backlog = db.get_backlog(order, descending)?;
+    let mut state = ListState::default();
+    state.select((!backlog.is_empty()).then_some(0));
+    loop {
+        let list = List::new(backlog.iter().enumerate().map(|(i, t)| {
+            ListItem::new(Line::styled(
+                format!(
+                    "{}: {} TSK-{} {}  {}",
+                    i,
+                    t.status,
+                    t.id,
+                    t.title,
+                    t.created.with_timezone(&Local).format(&config.date_format)
+                ),
+                config.theme.status(t.status),
+            ))
+        }))
+        .block(
+            Block::default()
+                .title(format!(
+                    "backlog ({} tasks, by {} {})",
+                    backlog.len(),
+                    match order {
+                        BacklogOrder::Created => "created",
+                        BacklogOrder::Status => "status",
+                    },
+                    if descending { "desc" } else { "asc" }
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(config.theme.text)
+        .highlight_style(config.theme.selected);
+        term.draw(|frame| {
+            let chunks = layout.split(frame.size());
+            frame.render_stateful_widget(list, chunks[0], &mut state);
+            frame.render_widget(command_editor.widget(), chunks[1]);
+        })?;
+        let input: Input = crossterm::event::read()?.into();
+        match (config.keys.backlog.action(&input), input) {
+            (Some(Action::Back), _) => return Ok(AppState::Home),
+            (Some(Action::Quit), _) => return Ok(AppState::Exit),
+            (Some(Action::OpenDetails), _) => {
+                match resolve_task(&backlog, state.selected(), None) {
+                    Some(task_id) => return Ok(AppState::Details(task_id)),
+                    None => show_error(&mut command_editor, &config.theme, "No such task"),
+                }
+            }
+            (_, Input { key: Key::Up, .. }) => {
+                state.select(state.selected().map(|i| i.saturating_sub(1)));
+            }
+            (_, Input { key: Key::Down, .. }) => {
+                state.select(
+                    state
+                        .selected()
+                        .map(|i| (i + 1).min(backlog.len().saturating_sub(1))),
+                );
+            }
+            (
+                _,
+                Input {
+                    key: Key::Enter, ..
+                },
+            ) => {
+                match parse_backlog_command(&config.expand_alias(&command_editor.lines()[0])) {
+                    Some(BacklogCommand::Sort(s)) => {
+                        if let Some((o, d)) = s.args() {
+                            (order, descending) = (*o, *d);
+                        }
+                    }
+                    Some(BacklogCommand::Push(p)) => {
+                        if let Some((task, position)) = p.args() {
+                            match resolve_task(&backlog, state.selected(), task.as_ref()) {
+                                Some(task_id) => {
+                                    let position = position.unwrap_or(0);
+                                    match db.insert_at(task_id, position) {
+                                        Ok(()) => command_editor.set_placeholder_text(format!(
+                                            "Pushed TSK-{task_id} to position {position}"
+                                        )),
+                                        Err(e) => show_error(
+                                            &mut command_editor,
+                                            &config.theme,
+                                            e.to_string(),
+                                        ),
+                                    }
+                                }
+                                None => {
+                                    show_error(&mut command_editor, &config.theme, "No such task")
+                                }
+                            }
+                        }
+                    }
+                    Some(BacklogCommand::Open(o)) => {
+                        let task_id = resolve_task(&backlog, state.selected(), o.args());
+                        match task_id.filter(|id| db.get_task(*id).is_ok()) {
+                            Some(task_id) => return Ok(AppState::Details(task_id)),
+                            None => show_error(&mut command_editor, &config.theme, "No such task"),
+                        }
+                    }
+                    Some(BacklogCommand::Quit(_)) => return Ok(AppState::Home),
+                    None => show_error(&mut command_editor, &config.theme, "Error parsing command"),
+                }
+                command_editor.delete_line_by_head();
+                backlog = db.get_backlog(order, descending)?;
+                state.select(match state.selected() {
+                    _ if backlog.is_empty() => None,
+                    Some(i) => Some(i.min(backlog.len() - 1)),
+                    None => Some(0),
+                });
+            }
+            (_, input) => {
+                if command_editor.input(input) {
+                    reset_placeholder(&mut command_editor, &config.theme);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::{Duration, Local, Utc};
+use ratatui::{
+    backend::Backend,
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Terminal,
+};
+use tui_textarea::{Input, Key, TextArea};
+
+use crate::{
+    commands::{parse_detail_command, Command, DetailCommand},
+    config::Config,
+    db::Db,
+    diff::{diff_lines, DiffLine},
+    error::Result,
+    keymap::Action,
+    timesheet,
+    types::{local_date, RelationshipSide, Revision, StatusChange, Task, TaskId, DATE_FORMAT},
+};
+
+use super::{format_duration, home::AppState, reset_placeholder, sessions, show_error};
+
+fn history_lines(history: &[StatusChange], config: &Config) -> Vec<Line<'static>> {
+    let now = Utc::now();
+    let timeline = history
+        .iter()
+        .enumerate()
+        .map(|(i, change)| {
+            if i + 1 < history.len() {
+                format!(
+                    "{:?} ({})",
+                    change.status,
+                    format_duration(change.duration(now))
+                )
+            } else {
+                format!("{:?}", change.status)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" → ");
+    let mut lines = vec![
+        Line::default(),
+        Line::styled("History", Style::default().add_modifier(Modifier::BOLD)),
+        Line::raw(format!("  {timeline}")),
+    ];
+    for change in history {
+        lines.push(Line::styled(
+            format!(
+                "  {}  {:<10} {}{}",
+                change
+                    .updated
+                    .with_timezone(&Local)
+                    .format(&config.date_format),
+                format!("{:?}", change.status),
+                format_duration(change.duration(now)),
+                if change.until.is_none() {
+                    " so far"
+                } else {
+                    ""
+                }
+            ),
+            config.theme.muted,
+        ));
+    }
+    lines
+}
+
+fn task_lines(
+    task: &Task,
+    spent: Duration,
+    focus_sessions: usize,
+    config: &Config,
+) -> Vec<Line<'static>> {
+    let label = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Status:  ", label),
+            Span::styled(
+                format!("{} {:?}", task.status, task.status),
+                config.theme.status(task.status),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("Created: ", label),
+            Span::raw(
+                task.created
+                    .with_timezone(&Local)
+                    .format(&config.date_format)
+                    .to_string(),
+            ),
+        ]),
+    ];
+    for (name, date) in [("Due:     ", task.due), ("Scheduled: ", task.scheduled)] {
+        if let Some(date) = date {
+            lines.push(Line::from(vec![
+                Span::styled(name, label),
+                Span::raw(local_date(date).format(DATE_FORMAT).to_string()),
+            ]));
+        }
+    }
+    if let Some(rule) = &task.recurrence {
+        lines.push(Line::from(vec![
+            Span::styled("Repeats: ", label),
+            Span::raw(rule.to_string()),
+        ]));
+    }
+    if spent != Duration::zero() {
+        lines.push(Line::from(vec![
+            Span::styled("Time:    ", label),
+            Span::raw(timesheet::hours(spent)),
+        ]));
+    }
+    if focus_sessions > 0 {
+        lines.push(Line::from(vec![
+            Span::styled("Focus:   ", label),
+            Span::raw(sessions(focus_sessions)),
+        ]));
+    }
+    if !task.tags.is_empty() {
+        let mut spans = vec![Span::styled("Tags:   ", label)];
+        for tag in &task.tags {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(tag.to_string(), config.theme.tag_style(tag)));
+        }
+        lines.push(Line::from(spans));
+    }
+    if let Some(link) = task.content.as_ref().and_then(|c| c.link.as_ref()) {
+        lines.push(Line::from(vec![
+            Span::styled("Link:    ", label),
+            Span::raw(link.to_string()),
+        ]));
+    }
+    if !task.relationships.is_empty() {
+        lines.push(Line::default());
+        lines.push(Line::styled("Relationships", label));
+        // relationships are sorted by tag, so each group is contiguous
+        let mut tag: Option<&str> = None;
+        for r in &task.relationships {
+            if tag != Some(r.tag.as_str()) {
+                tag = Some(r.tag.as_str());
+                lines.push(Line::raw(format!("  {}", r.tag)));
+            }
+            lines.push(Line::raw(match r.side {
+                RelationshipSide::Left(other) => {
+                    format!("    ← TSK-{other} {}", r.title)
+                }
+                RelationshipSide::Right(other) => {
+                    format!("    → TSK-{other} {}", r.title)
+                }
+            }));
+        }
+    }
+    if let Some(body) = task.content.as_ref().and_then(|c| c.body.as_ref()) {
+        lines.push(Line::default());
+        lines.extend(body.lines().map(|l| Line::raw(l.to_string())));
+    }
+    lines
+}
+
+fn revision_lines(revisions: &[Revision], config: &Config) -> Vec<Line<'static>> {
+    if revisions.is_empty() {
+        return vec![Line::raw("This task has no content yet.")];
+    }
+    revisions
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let body = r
+                .content
+                .body
+                .as_deref()
+                .and_then(|b| b.lines().next())
+                .unwrap_or("(no body)");
+            let link = r
+                .content
+                .link
+                .as_ref()
+                .map(|l| format!("  {l}"))
+                .unwrap_or_default();
+            let current = if i + 1 == revisions.len() {
+                " (current)"
+            } else {
+                ""
+            };
+            Line::raw(format!(
+                "#{}  {}  {body}{link}{current}",
+                i + 1,
+                r.updated.with_timezone(&Local).format(&config.date_format),
+            ))
+        })
+        .collect()
+}
+
+fn revision_diff_lines(
+    revisions: &[Revision],
+    from: usize,
+    to: usize,
+    config: &Config,
+) -> Vec<Line<'static>> {
+    let (old, new) = (&revisions[from - 1].content, &revisions[to - 1].content);
+    let (removed, added) = (config.theme.removed, config.theme.added);
+    let mut lines = vec![Line::styled(
+        format!("Revision #{from} → #{to}"),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    let (old_link, new_link) = (
+        old.link.as_ref().map(|l| l.to_string()),
+        new.link.as_ref().map(|l| l.to_string()),
+    );
+    if old_link != new_link {
+        if let Some(link) = old_link {
+            lines.push(Line::styled(format!("- link: {link}"), removed));
+        }
+        if let Some(link) = new_link {
+            lines.push(Line::styled(format!("+ link: {link}"), added));
+        }
+    }
+    lines.push(Line::default());
+    let (old_body, new_body) = (
+        old.body.as_deref().unwrap_or_default(),
+        new.body.as_deref().unwrap_or_default(),
+    );
+    lines.extend(
+        diff_lines(old_body, new_body)
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(l) => Line::raw(format!("  {l}")),
+                DiffLine::Added(l) => Line::styled(format!("+ {l}"), added),
+                DiffLine::Removed(l) => Line::styled(format!("- {l}"), removed),
+            }),
+    );
+    lines
+}
+
+/// What the main area of the details view is showing.
+enum Pane {
+    Details,
+    Revisions,
+    Diff(usize, usize),
+}
+
+/// `render_details` shows everything known about a single task. Revisions of the task's content
+/// can be listed, diffed and restored from the command editor. Esc returns to the home view.
+pub(crate) fn render_details<B: Backend>(
+    term: &mut Terminal<B>,
+    db: &mut Db,
+    config: &Config,
+    task_id: TaskId,
+) -> Result<AppState> {
+    let layout = Layout::default()
+        .constraints([Constraint::Min(1), Constraint::Length(3)])
+        .direction(Direction::Vertical);
+    let mut command_editor = TextArea::default();
+    command_editor.set_cursor_line_style(Style::default());
+    command_editor.set_block(Block::default().borders(Borders::ALL));
+    command_editor.set_style(config.theme.text);
+    reset_placeholder(&mut command_editor, &config.theme);
+    let mut task = db.get_task(task_id)?;
+    let mut revisions = db.get_revisions(task_id)?;
+    let mut pane = Pane::Details;
+    loop {
+        let (title, lines) = match pane {
+            Pane::Details => {
+                let mut lines = task_lines(
+                    &task,
+                    db.time_spent(task_id, Utc::now())?,
+                    db.get_focus_sessions(task_id)?.len(),
+                    config,
+                );
+                lines.extend(history_lines(&db.get_status_history(task_id)?, config));
+                (format!("TSK-{} {}", task.id, task.title), lines)
+            }
+            Pane::Revisions => (
+                format!("TSK-{} revisions", task.id),
+                revision_lines(&revisions, config),
+            ),
+            Pane::Diff(from, to) => (
+                format!("TSK-{} diff", task.id),
+                revision_diff_lines(&revisions, from, to, config),
+            ),
+        };
+        let details = Paragraph::new(lines)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .style(config.theme.text)
+            .wrap(Wrap { trim: false });
+        term.draw(|frame| {
+            let chunks = layout.split(frame.size());
+            frame.render_widget(details, chunks[0]);
+            frame.render_widget(command_editor.widget(), chunks[1]);
+        })?;
+        let input: Input = crossterm::event::read()?.into();
+        match (config.keys.details.action(&input), input) {
+            (Some(Action::Back), _) => match pane {
+                Pane::Details => return Ok(AppState::Home),
+                _ => pane = Pane::Details,
+            },
+            (Some(Action::Quit), _) => return Ok(AppState::Exit),
+            (
+                _,
+                Input {
+                    key: Key::Enter, ..
+                },
+            ) => {
+                match parse_detail_command(&config.expand_alias(&command_editor.lines()[0])) {
+                    Some(DetailCommand::Revisions(_)) => pane = Pane::Revisions,
+                    Some(DetailCommand::Diff(d)) => {
+                        if let Some((from, to)) = d.args() {
+                            let to = to.unwrap_or(revisions.len());
+                            let valid = 1..=revisions.len();
+                            if valid.contains(from) && valid.contains(&to) {
+                                pane = Pane::Diff(*from, to);
+                            } else {
+                                show_error(&mut command_editor, &config.theme, "No such revision");
+                            }
+                        }
+                    }
+                    Some(DetailCommand::Restore(r)) => {
+                        if let Some(n) = r.args() {
+                            if let Some(revision) = n.checked_sub(1).and_then(|i| revisions.get(i))
+                            {
+                                db.restore_revision(task_id, revision.updated)?;
+                                command_editor
+                                    .set_placeholder_text(format!("Restored revision #{n}"));
+                                task = db.get_task(task_id)?;
+                                revisions = db.get_revisions(task_id)?;
+                                pane = Pane::Revisions;
+                            } else {
+                                show_error(&mut command_editor, &config.theme, "No such revision");
+                            }
+                        }
+                    }
+                    Some(DetailCommand::Quit(_)) => return Ok(AppState::Home),
+                    None => show_error(&mut command_editor, &config.theme, "Error parsing command"),
+                }
+                command_editor.delete_line_by_head();
+            }
+            (_, input) => {
+                if command_editor.input(input) {
+                    reset_placeholder(&mut command_editor, &config.theme);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::{DateTime, Duration, Utc};
+use ratatui::widgets::{Block, Borders, Gauge};
+use std::io::{self, Write};
+
+use crate::{
+    db::Db,
+    error::Result,
+    theme::Theme,
+    types::{TaskId, TaskStatus},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    /// The task is in progress and the timer is counting down
+    Work,
+    /// The session ran its full length, waiting for complete, continue or break
+    Over,
+    /// The task is paused until the break is over
+    Break,
+}
+
+/// A focus session on a single task, shown as a countdown in the home view.
+#[derive(Debug, Clone)]
+pub(crate) struct Focus {
+    pub task_id: TaskId,
+    pub title: String,
+    pub phase: Phase,
+    /// When the current phase started
+    pub started: DateTime<Utc>,
+    /// How long the current phase runs for
+    pub length: Duration,
+    /// How long a session runs for, kept for `continue`
+    pub session: Duration,
+}
+
+impl Focus {
+    /// `start` puts `task_id` in progress, unless it already is, and starts a session of
+    /// `length` on it.
+    pub(crate) fn start(
+        db: &mut Db,
+        task_id: TaskId,
+        length: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Focus> {
+        let task = db.get_task(task_id)?;
+        if task.status != TaskStatus::InProgress {
+            db.update_status(task_id, TaskStatus::InProgress)?;
+        }
+        Ok(Focus {
+            task_id,
+            title: task.title,
+            phase: Phase::Work,
+            started: now,
+            length,
+            session: length,
+        })
+    }
+
+    /// `restart` starts another session of the same length on the task, after a break or once
+    /// the last session is over.
+    pub(crate) fn restart(&mut self, db: &mut Db, now: DateTime<Utc>) -> Result<()> {
+        *self = Focus::start(db, self.task_id, self.session, now)?;
+        Ok(())
+    }
+
+    /// `take_break` pauses the task for `length`.
+    pub(crate) fn take_break(
+        &mut self,
+        db: &mut Db,
+        length: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        if db.get_task(self.task_id)?.status == TaskStatus::InProgress {
+            db.pause(self.task_id)?;
+        }
+        self.phase = Phase::Break;
+        self.started = now;
+        self.length = length;
+        Ok(())
+    }
+
+    pub(crate) fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        (self.started + self.length - now).max(Duration::zero())
+    }
+
+    /// `tick` moves on from a phase whose time is up, logging a finished session against the
+    /// task, and returns the phase that ended.
+    pub(crate) fn tick(&mut self, db: &mut Db, now: DateTime<Utc>) -> Result<Option<Phase>> {
+        if self.phase == Phase::Over || self.remaining(now) > Duration::zero() {
+            return Ok(None);
+        }
+        let ended = self.phase;
+        if ended == Phase::Work {
+            db.log_focus(self.task_id, self.started, self.length)?;
+        }
+        self.phase = Phase::Over;
+        Ok(Some(ended))
+    }
+
+    pub(crate) fn gauge(&self, now: DateTime<Utc>, theme: &Theme) -> Gauge<'static> {
+        let remaining = self.remaining(now);
+        let ratio = if self.phase == Phase::Over || self.length <= Duration::zero() {
+            1.0
+        } else {
+            1.0 - remaining.num_milliseconds() as f64 / self.length.num_milliseconds() as f64
+        };
+        let label = match self.phase {
+            Phase::Work => format!(
+                "TSK-{} {}  {} left",
+                self.task_id,
+                self.title,
+                countdown(remaining)
+            ),
+            Phase::Over => format!(
+                "TSK-{} {}  complete, continue or break",
+                self.task_id, self.title
+            ),
+            Phase::Break => format!("Break  {} left", countdown(remaining)),
+        };
+        Gauge::default()
+            .block(Block::default().title("focus").borders(Borders::ALL))
+            .gauge_style(theme.focus)
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label)
+    }
+}
+
+/// `countdown` formats `remaining` as minutes and seconds, e.g. `24:59`.
+fn countdown(remaining: Duration) -> String {
+    let seconds = remaining.num_seconds();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// `ring_bell` rings the terminal bell, which also works from the alternate screen.
+pub(crate) fn ring_bell() -> Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_focus() -> Result<()> {
+        let mut db = Db::in_memory()?;
+        let task_id = db.push_task("write docs".to_string())?;
+        let now = DateTime::from_timestamp(1_000_000, 0).unwrap();
+        let mut focus = Focus::start(&mut db, task_id, Duration::minutes(25), now)?;
+        assert_eq!(focus.phase, Phase::Work);
+        assert_eq!(db.get_task(task_id)?.status, TaskStatus::InProgress);
+
+        let almost = now + Duration::minutes(24);
+        assert_eq!(focus.tick(&mut db, almost)?, None);
+        assert_eq!(focus.remaining(almost), Duration::minutes(1));
+        assert!(db.get_focus_sessions(task_id)?.is_empty());
+
+        // Work → Over logs the session once, however often it ticks after that
+        let over = now + Duration::minutes(25);
+        assert_eq!(focus.tick(&mut db, over)?, Some(Phase::Work));
+        assert_eq!(focus.phase, Phase::Over);
+        assert_eq!(focus.tick(&mut db, over + Duration::minutes(5))?, None);
+        assert_eq!(
+            db.get_focus_sessions(task_id)?,
+            vec![(now, Duration::minutes(25))]
+        );
+
+        // continue
+        let again = over + Duration::minutes(10);
+        focus.restart(&mut db, again)?;
+        assert_eq!(focus.phase, Phase::Work);
+        assert_eq!(focus.started, again);
+        assert_eq!(focus.length, Duration::minutes(25));
+
+        // Break during a session pauses the task without logging the partial session
+        let pause = again + Duration::minutes(10);
+        focus.take_break(&mut db, Duration::minutes(5), pause)?;
+        assert_eq!(focus.phase, Phase::Break);
+        assert_eq!(db.get_task(task_id)?.status, TaskStatus::Todo);
+        assert_eq!(focus.tick(&mut db, pause + Duration::minutes(4))?, None);
+        assert_eq!(
+            focus.tick(&mut db, pause + Duration::minutes(5))?,
+            Some(Phase::Break)
+        );
+        assert_eq!(focus.phase, Phase::Over);
+        assert_eq!(db.get_focus_sessions(task_id)?.len(), 1);
+
+        // continue after the break puts the task back in progress for a full session
+        let back = pause + Duration::minutes(6);
+        focus.restart(&mut db, back)?;
+        assert_eq!(focus.phase, Phase::Work);
+        assert_eq!(focus.length, Duration::minutes(25));
+        assert_eq!(db.get_task(task_id)?.status, TaskStatus::InProgress);
+        assert_eq!(
+            focus.tick(&mut db, back + Duration::minutes(25))?,
+            Some(Phase::Work)
+        );
+        assert_eq!(
+            db.get_focus_sessions(task_id)?,
+            vec![(now, Duration::minutes(25)), (back, Duration::minutes(25))]
+        );
+        Ok(())
+    }
+}
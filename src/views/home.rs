@@ -1,11 +1,14 @@
 #![allow(dead_code, unused_imports)]
 use crate::{
-    commands::{self, parse_home_command, Command, HomeCommand, Push},
+    commands::{self, parse_home_command, Command, HomeCommand, Push, TaskOrRelative},
     config::Config,
     db::Db,
     error::{Error, Result},
-    types::{Task, TaskId, TaskStatus},
+    keymap::Action,
+    markdown, timesheet,
+    types::{local_date, start_of_day, Day, QueryArgs, Task, TaskId, TaskStatus, DATE_FORMAT},
 };
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
 use crossterm::{
     event::{self, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -15,22 +18,168 @@ use log::{debug, error};
 use ratatui::{
     prelude::*,
     style::Style,
-    widgets::{Block, Borders, List, ListDirection},
+    widgets::{Block, Borders, List, ListDirection, ListItem},
     Frame,
 };
+use std::fs;
 use tui_textarea::{Input, Key, TextArea};
 
+use super::{
+    focus::{ring_bell, Focus, Phase},
+    reset_placeholder, show_error,
+};
+
+/// How often the home view redraws while a focus timer runs.
+const TICK: std::time::Duration = std::time::Duration::from_millis(250);
+
 pub(crate) enum AppState {
     Home,
-    Details,
-    Query,
+    Details(TaskId),
+    Query(Vec<QueryArgs>),
+    Backlog,
     Exit,
 }
 
+fn resolve_task(tasks: &[Task], task: &TaskOrRelative) -> Option<TaskId> {
+    match task {
+        TaskOrRelative::Task(task_id) => Some(*task_id),
+        TaskOrRelative::Relative(i) => tasks.get(*i as usize).map(|t| t.id),
+    }
+}
+
+/// `pause` stops the clock on `task`, by default the first task in progress on the stack.
+fn pause(db: &mut Db, tasks: &[Task], task: Option<&TaskOrRelative>) -> Result<String> {
+    let task_id = match task {
+        Some(task) => resolve_task(tasks, task),
+        None => tasks
+            .iter()
+            .find(|t| t.status == TaskStatus::InProgress)
+            .map(|t| t.id),
+    }
+    .ok_or_else(|| Error::NotFound("No task in progress".to_string()))?;
+    db.pause(task_id)?;
+    Ok(format!(
+        "Paused TSK-{task_id} at {}",
+        timesheet::hours(db.time_spent(task_id, Utc::now())?)
+    ))
+}
+
+/// `resume` starts the clock again on `task`, by default the task paused last or else the top
+/// task.
+fn resume(db: &mut Db, tasks: &[Task], task: Option<&TaskOrRelative>) -> Result<String> {
+    let task_id = match task {
+        Some(task) => resolve_task(tasks, task),
+        None => db.last_paused()?.or(tasks.first().map(|t| t.id)),
+    }
+    .ok_or_else(|| Error::NotFound("No such task".to_string()))?;
+    db.resume(task_id)?;
+    Ok(format!("Resumed TSK-{task_id}"))
+}
+
+/// `log_time` adds `time` spent on `task`, the top task if it's left out, on `day`, today by
+/// default.
+fn log_time(
+    db: &mut Db,
+    tasks: &[Task],
+    task: &Option<TaskOrRelative>,
+    time: Duration,
+    day: &Option<Day>,
+) -> Result<String> {
+    let task_id = match task {
+        Some(task) => resolve_task(tasks, task),
+        None => tasks.first().map(|t| t.id),
+    }
+    .ok_or_else(|| Error::NotFound("No such task".to_string()))?;
+    let day = day.unwrap_or(Day::Today);
+    db.log_time(task_id, start_of_day(day.date()), time)?;
+    Ok(format!(
+        "Logged {} on TSK-{task_id} {day}",
+        timesheet::hours(time)
+    ))
+}
+
+/// `focus_over` is whether `focus` should stop because its task was completed, dropped from
+/// work or paused mid-session.
+fn focus_over(db: &Db, focus: &Focus) -> bool {
+    match db.get_task(focus.task_id).map(|t| t.status) {
+        Ok(TaskStatus::InProgress) => false,
+        Ok(TaskStatus::Todo) => focus.phase == Phase::Work,
+        _ => true,
+    }
+}
+
+/// `set_date` sets or clears the due or scheduled date of `task`, the top task if it's left out.
+fn set_date(
+    db: &mut Db,
+    tasks: &[Task],
+    task: &Option<TaskOrRelative>,
+    day: &Option<Day>,
+    name: &str,
+    set: fn(&mut Db, TaskId, Option<DateTime<Utc>>) -> Result<()>,
+) -> Result<String> {
+    let task_id = match task {
+        Some(task) => resolve_task(tasks, task),
+        None => tasks.first().map(|t| t.id),
+    }
+    .ok_or_else(|| Error::NotFound("No such task".to_string()))?;
+    let date = day.map(|day| day.date());
+    set(db, task_id, date.map(start_of_day))?;
+    Ok(match date {
+        Some(date) => format!("TSK-{task_id} is {name} {}", date.format(DATE_FORMAT)),
+        None => format!("TSK-{task_id} is no longer {name}"),
+    })
+}
+
+/// `date_span` notes a task's due date, highlighted when it has passed or is within
+/// `due_soon_days`, or its scheduled date while that is still ahead.
+fn date_span(task: &Task, today: NaiveDate, config: &Config) -> Option<Span<'static>> {
+    if let Some(due) = task.due.map(local_date) {
+        let date = due.format(DATE_FORMAT);
+        let days = (due - today).num_days();
+        return Some(if days < 0 {
+            Span::styled(format!(" (overdue, due {date})"), config.theme.overdue)
+        } else if days <= config.due_soon_days as i64 {
+            Span::styled(format!(" (due {date})"), config.theme.due_soon)
+        } else {
+            Span::styled(format!(" (due {date})"), config.theme.muted)
+        });
+    }
+    task.scheduled
+        .map(local_date)
+        .filter(|scheduled| *scheduled > today)
+        .map(|scheduled| {
+            Span::styled(
+                format!(" (scheduled {})", scheduled.format(DATE_FORMAT)),
+                config.theme.muted,
+            )
+        })
+}
+
+/// `action_command` is the command a key binding runs in the home view. Bindings act on the top
+/// task, like their commands do without arguments.
+fn action_command(action: Action) -> HomeCommand {
+    match action {
+        Action::Quit | Action::Back => HomeCommand::Quit(Default::default()),
+        Action::CompleteTop => HomeCommand::Complete(Default::default()),
+        Action::Swap => HomeCommand::Swap(Default::default()),
+        Action::OpenDetails => HomeCommand::Open(Default::default()),
+        Action::OpenQuery => HomeCommand::Query(Default::default()),
+        Action::Undo => HomeCommand::Undo(Default::default()),
+    }
+}
+
+/// `session_over` is the placeholder shown once the focus session on `task_id` has run out.
+fn session_over(task_id: TaskId) -> String {
+    format!("Focus on TSK-{task_id} is over: complete, continue or break")
+}
+
+/// `render_home` shows the top of the stack. `focus` is owned by the caller so a session keeps
+/// running while another view is open; it only ticks while home is shown.
 pub(crate) fn render_home<B: Backend>(
     term: &mut Terminal<B>,
     db: &mut Db,
     config: &Config,
+    focus: &mut Option<Focus>,
 ) -> Result<AppState> {
     let mut tasks = db.get_top_n_tasks(config.num_top_tasks)?;
     let layout = Layout::default()
@@ -38,158 +187,401 @@ pub(crate) fn render_home<B: Backend>(
         .direction(Direction::Vertical);
     let mut command_editor = TextArea::default();
     command_editor.set_cursor_line_style(Style::default());
-    command_editor.set_placeholder_text("Enter a command...");
-    command_editor.set_placeholder_style(
-        Style::default()
-            .fg(Color::DarkGray)
-            .add_modifier(Modifier::ITALIC),
-    );
-    command_editor.set_style(Style::default().fg(Color::White));
-    let mut count = 0;
+    reset_placeholder(&mut command_editor, &config.theme);
+    command_editor.set_style(config.theme.text);
+    // the task may have been completed or dropped in another view
+    if focus.as_ref().is_some_and(|f| focus_over(db, f)) {
+        *focus = None;
+    }
+    if let Some(f) = focus.as_ref().filter(|f| f.phase == Phase::Over) {
+        command_editor.set_placeholder_text(session_over(f.task_id));
+    }
     loop {
-        let list = List::new(
-            tasks
-                .iter()
-                .enumerate()
-                .map(|(i, t)| format!("{}: {} TSK-{} {}", i, t.status, t.id, t.title.as_str())),
-        )
-        .block(Block::default().title("tasks").borders(Borders::ALL))
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().add_modifier(Modifier::ITALIC))
-        .direction(ListDirection::BottomToTop);
+        if let Some(f) = focus.as_mut() {
+            match f.tick(db, Utc::now())? {
+                Some(Phase::Work) => {
+                    ring_bell()?;
+                    command_editor.set_placeholder_text(session_over(f.task_id));
+                }
+                Some(Phase::Break) => {
+                    ring_bell()?;
+                    command_editor
+                        .set_placeholder_text("Break is over: continue to start another session");
+                }
+                _ => {}
+            }
+        }
+        let mut items = Vec::with_capacity(tasks.len());
+        let today = Local::now().date_naive();
+        for (i, t) in tasks.iter().enumerate() {
+            let mut spans = vec![Span::styled(
+                format!("{}: {} TSK-{} {}", i, t.status, t.id, t.title.as_str()),
+                config.theme.status(t.status),
+            )];
+            spans.extend(date_span(t, today, config));
+            let blockers = db.open_blockers(t.id)?;
+            if !blockers.is_empty() {
+                let blockers = blockers
+                    .iter()
+                    .map(|id| format!("TSK-{id}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                spans.push(Span::styled(
+                    format!(" (blocked by {blockers})"),
+                    config.theme.error,
+                ));
+            }
+            items.push(ListItem::new(Line::from(spans)));
+        }
+        let list = List::new(items)
+            .block(Block::default().title("tasks").borders(Borders::ALL))
+            .style(config.theme.text)
+            .highlight_style(config.theme.selected)
+            .direction(ListDirection::BottomToTop);
+        let gauge = focus.as_ref().map(|f| f.gauge(Utc::now(), &config.theme));
         term.draw(|frame| {
-            let chunks = layout.split(frame.size());
+            let chunks = match gauge {
+                Some(_) => Layout::default()
+                    .constraints([
+                        Constraint::Min(1),
+                        Constraint::Length(3),
+                        Constraint::Length(3),
+                    ])
+                    .direction(Direction::Vertical)
+                    .split(frame.size()),
+                None => layout.split(frame.size()),
+            };
             frame.render_widget(list, chunks[0]);
-            frame.render_widget(command_editor.widget(), chunks[1]);
+            if let Some(gauge) = gauge {
+                frame.render_widget(gauge, chunks[1]);
+            }
+            frame.render_widget(command_editor.widget(), chunks[chunks.len() - 1]);
         })?;
-        match crossterm::event::read()?.into() {
-            Input { key: Key::Esc, .. }
-            | Input {
-                key: Key::Char('q'),
-                ctrl: true,
-                ..
-            } => break,
-            Input {
-                key: Key::Char('m'),
-                ctrl: true,
-                ..
-            } => {}
-            Input {
-                key: Key::Enter, ..
-            } => {
-                if let Some(c) = parse_home_command(command_editor.lines()[0].as_str()) {
-                    match c {
-                        HomeCommand::Push(p) => {
-                            if let Some(a) = p.args() {
-                                let task_id = db.create_task((*a).clone())?;
-                                db.prioritize(task_id)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+        // while a timer runs, redraw on every tick rather than waiting for input
+        if focus.is_some() && !event::poll(TICK)? {
+            continue;
+        }
+        let input: Input = crossterm::event::read()?.into();
+        let command = match config.keys.home.action(&input) {
+            Some(action) => Some(action_command(action)),
+            None => match input {
+                Input {
+                    key: Key::Enter, ..
+                } => {
+                    let command =
+                        parse_home_command(&config.expand_alias(&command_editor.lines()[0]));
+                    if command.is_none() {
+                        show_error(&mut command_editor, &config.theme, "Error parsing command");
+                    }
+                    command_editor.delete_line_by_head();
+                    command
+                }
+                input => {
+                    if command_editor.input(input) {
+                        reset_placeholder(&mut command_editor, &config.theme);
+                    }
+                    None
+                }
+            },
+        };
+        if let Some(c) = command {
+            match c {
+                HomeCommand::Push(p) => {
+                    if let Some(a) = p.args() {
+                        db.push_task((*a).clone())?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Edit(_) => unimplemented!("Edit command isn't implemented."),
+                HomeCommand::Drop(c) => {
+                    let task_id = if let Some(task_id) = c.args() {
+                        Some(*task_id)
+                    } else {
+                        tasks.first().map(|t| t.id)
+                    };
+                    if let Some(task_id) = task_id {
+                        db.deprioritize(task_id)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Complete(c) => {
+                    // once a session is over, `complete` means the task that was focused on
+                    let task_id = if let Some(task_id) = c.args() {
+                        Some(*task_id)
+                    } else if let Some(f) = focus.as_ref().filter(|f| f.phase == Phase::Over) {
+                        Some(f.task_id)
+                    } else {
+                        tasks.first().map(|t| t.id)
+                    };
+                    if let Some(task_id) = task_id {
+                        match db.complete(task_id, config.recurring_position) {
+                            Err(Error::Blocked(e)) => {
+                                show_error(&mut command_editor, &config.theme, e)
                             }
+                            Ok(Some(next)) => command_editor.set_placeholder_text(format!(
+                                "TSK-{task_id} repeats as TSK-{next}"
+                            )),
+                            Ok(None) => {}
+                            Err(e) => show_error(&mut command_editor, &config.theme, e.to_string()),
                         }
-                        HomeCommand::Edit(_) => unimplemented!("Edit command isn't implemented."),
-                        HomeCommand::Drop(c) => {
-                            let task_id = if let Some(task_id) = c.args() {
-                                Some(*task_id)
-                            } else {
-                                tasks.first().map(|t| t.id)
-                            };
-                            if let Some(task_id) = task_id {
-                                db.deprioritize(task_id)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Swap(_) => {
+                    if tasks.len() >= 2 {
+                        let second = &tasks[1];
+                        db.prioritize(second.id)?;
+                        command_editor
+                            .set_placeholder_text(format!("Prioritized TSK-{}", second.id));
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Quit(_) => break,
+                HomeCommand::Undo(_) => {
+                    command_editor.set_placeholder_text(match db.undo()? {
+                        Some(name) => format!("Undid {name}"),
+                        None => "Nothing to undo".to_string(),
+                    });
+                    tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                }
+                HomeCommand::Redo(_) => {
+                    command_editor.set_placeholder_text(match db.redo()? {
+                        Some(name) => format!("Redid {name}"),
+                        None => "Nothing to redo".to_string(),
+                    });
+                    tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                }
+                HomeCommand::Start(_) => {
+                    if !tasks.is_empty() {
+                        let first: &mut Task = &mut tasks[0];
+                        match db.update_status(first.id, TaskStatus::InProgress) {
+                            Ok(()) => first.status = TaskStatus::InProgress,
+                            Err(Error::Blocked(e)) => {
+                                show_error(&mut command_editor, &config.theme, e)
                             }
+                            Err(e) => return Err(e),
                         }
-                        HomeCommand::Complete(c) => {
-                            let task_id = if let Some(task_id) = c.args() {
-                                Some(*task_id)
-                            } else {
-                                tasks.first().map(|t| t.id)
-                            };
-                            if let Some(task_id) = task_id {
-                                db.update_status(task_id, TaskStatus::Complete)?;
-                                db.deprioritize(task_id)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Todo(_) => {
+                    if !tasks.is_empty() {
+                        let first: &mut Task = &mut tasks[0];
+                        db.update_status(first.id, TaskStatus::Todo)?;
+                        first.status = TaskStatus::Todo;
+                    }
+                }
+                HomeCommand::Reprioritize(r) => {
+                    if let Some(task_id) = r.args() {
+                        db.prioritize(*task_id)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Rot(_) => {
+                    if tasks.len() >= 3 {
+                        let third = tasks[2].id;
+                        let first = tasks.first().map(|t| t.id).unwrap();
+                        db.set_next_of(first, third)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::NRot(_) => {
+                    if tasks.len() >= 3 {
+                        let third = tasks[2].id;
+                        db.prioritize(third)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Backlog(_) => return Ok(AppState::Backlog),
+                HomeCommand::Query(q) => {
+                    return Ok(AppState::Query(q.args().cloned().unwrap_or_default()));
+                }
+                HomeCommand::Open(o) => {
+                    let task_id = match o.args() {
+                        Some(task) => resolve_task(&tasks, task),
+                        None => tasks.first().map(|t| t.id),
+                    };
+                    if let Some(task_id) = task_id.filter(|id| db.get_task(*id).is_ok()) {
+                        return Ok(AppState::Details(task_id));
+                    }
+                    show_error(&mut command_editor, &config.theme, "No such task");
+                }
+                HomeCommand::Connect(c) => {
+                    if let Some((left, tag, right)) = c.args() {
+                        let result = match (resolve_task(&tasks, left), resolve_task(&tasks, right))
+                        {
+                            (Some(left), Some(right)) => db
+                                .connect(left, tag, right)
+                                .map(|_| format!("TSK-{left} {tag} TSK-{right}")),
+                            _ => Err(Error::Database("No such task".to_string())),
+                        };
+                        match result {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Swap(_) => {
-                            if tasks.len() >= 2 {
-                                let second = &tasks[1];
-                                db.prioritize(second.id)?;
-                                command_editor
-                                    .set_placeholder_text(format!("Prioritized TSK-{}", second.id));
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Disconnect(c) => {
+                    if let Some((left, tag, right)) = c.args() {
+                        let result = match (resolve_task(&tasks, left), resolve_task(&tasks, right))
+                        {
+                            (Some(left), Some(right)) => db
+                                .disconnect(left, tag, right)
+                                .map(|_| format!("Removed TSK-{left} {tag} TSK-{right}")),
+                            _ => Err(Error::Database("No such task".to_string())),
+                        };
+                        match result {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Quit(_) => break,
-                        HomeCommand::Start(_) => {
-                            if tasks.len() > 0 {
-                                let first: &mut Task = &mut tasks[0];
-                                db.update_status(first.id, TaskStatus::InProgress)?;
-                                first.status = TaskStatus::InProgress;
+                    }
+                }
+                HomeCommand::Markdown(m) => {
+                    if let Some((history, file)) = m.args() {
+                        let written = markdown::render(db, config, *history)
+                            .and_then(|text| Ok(fs::write(file, text)?));
+                        match written {
+                            Ok(()) => command_editor.set_placeholder_text(format!("Wrote {file}")),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Todo(_) => {
-                            if tasks.len() > 0 {
-                                let first: &mut Task = &mut tasks[0];
-                                db.update_status(first.id, TaskStatus::Todo)?;
-                                first.status = TaskStatus::Todo;
+                    }
+                }
+                HomeCommand::Due(d) => {
+                    if let Some((task, day)) = d.args() {
+                        match set_date(db, &tasks, task, day, "due", Db::set_due) {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Reprioritize(r) => {
-                            if let Some(task_id) = r.args() {
-                                db.prioritize(*task_id)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Schedule(s) => {
+                    if let Some((task, day)) = s.args() {
+                        match set_date(db, &tasks, task, day, "scheduled", Db::set_scheduled) {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Rot(_) => {
-                            if tasks.len() >= 3 {
-                                let third = tasks[2].id;
-                                let first = tasks.first().map(|t| t.id).unwrap();
-                                db.set_next_of(first, third)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                }
+                HomeCommand::Repeat(r) => {
+                    if let Some((task, rule)) = r.args() {
+                        let task_id = match task {
+                            Some(task) => resolve_task(&tasks, task),
+                            None => tasks.first().map(|t| t.id),
+                        };
+                        let result = match task_id {
+                            Some(task_id) => {
+                                db.set_recurrence(task_id, rule.as_ref())
+                                    .map(|_| match rule {
+                                        Some(rule) => format!("TSK-{task_id} repeats {rule}"),
+                                        None => format!("TSK-{task_id} no longer repeats"),
+                                    })
                             }
-                        }
-                        HomeCommand::NRot(_) => {
-                            if tasks.len() >= 3 {
-                                let third = tasks[2].id;
-                                db.prioritize(third)?;
-                                tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                            None => Err(Error::NotFound("No such task".to_string())),
+                        };
+                        match result {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
-                        HomeCommand::Make(m) => {
-                            if let Some(name) = m.args() {
-                                let _ = db.make_tag(name).map_err(|_| {
-                                    command_editor.set_placeholder_style(
-                                        Style::default()
-                                            .fg(Color::Red)
-                                            .add_modifier(Modifier::ITALIC),
-                                    );
-                                    command_editor.set_placeholder_text(format!(
-                                        "Unable to create tag {name}"
-                                    ));
-                                });
+                    }
+                }
+                HomeCommand::Pause(p) => {
+                    match pause(db, &tasks, p.args()) {
+                        Ok(message) => command_editor.set_placeholder_text(message),
+                        Err(e) => show_error(&mut command_editor, &config.theme, e.to_string()),
+                    }
+                    tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                }
+                HomeCommand::Resume(r) => {
+                    match resume(db, &tasks, r.args()) {
+                        Ok(message) => command_editor.set_placeholder_text(message),
+                        Err(e) => show_error(&mut command_editor, &config.theme, e.to_string()),
+                    }
+                    tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                }
+                HomeCommand::Log(l) => {
+                    if let Some((task, time, day)) = l.args() {
+                        match log_time(db, &tasks, task, *time, day) {
+                            Ok(message) => command_editor.set_placeholder_text(message),
+                            Err(e) => {
+                                show_error(&mut command_editor, &config.theme, e.to_string());
                             }
                         }
                     }
-                } else {
-                    command_editor.set_placeholder_style(
-                        Style::default()
-                            .fg(Color::Red)
-                            .add_modifier(Modifier::ITALIC),
-                    );
-                    command_editor.set_placeholder_text("Error parsing command");
                 }
-                command_editor.delete_line_by_head();
-            }
-            input => {
-                if command_editor.input(input) {
-                    command_editor.set_placeholder_style(
-                        Style::default()
-                            .fg(Color::DarkGray)
-                            .add_modifier(Modifier::ITALIC),
-                    );
-                    command_editor.set_placeholder_text("Enter a command...");
-                    count += 1;
+                HomeCommand::Focus(f) => {
+                    let minutes = f.args().copied().unwrap_or(config.focus_minutes);
+                    let started = match tasks.first() {
+                        Some(task) => {
+                            Focus::start(db, task.id, Duration::minutes(minutes.into()), Utc::now())
+                        }
+                        None => Err(Error::NotFound("The stack is empty".to_string())),
+                    };
+                    match started {
+                        Ok(f) => {
+                            command_editor.set_placeholder_text(format!(
+                                "Focusing on TSK-{} until {}",
+                                f.task_id,
+                                (f.started + f.length).with_timezone(&Local).format("%H:%M")
+                            ));
+                            *focus = Some(f);
+                        }
+                        Err(e) => show_error(&mut command_editor, &config.theme, e.to_string()),
+                    }
+                    tasks = db.get_top_n_tasks(config.num_top_tasks)?;
                 }
+                HomeCommand::Continue(_) => match focus.as_mut() {
+                    Some(f) if f.phase == Phase::Over => {
+                        match f.restart(db, Utc::now()) {
+                            Ok(()) => reset_placeholder(&mut command_editor, &config.theme),
+                            Err(e) => show_error(&mut command_editor, &config.theme, e.to_string()),
+                        }
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                    _ => show_error(
+                        &mut command_editor,
+                        &config.theme,
+                        "No focus session is over",
+                    ),
+                },
+                HomeCommand::Break(b) => match focus.as_mut() {
+                    Some(f) if f.phase != Phase::Break => {
+                        let minutes = b.args().copied().unwrap_or(config.break_minutes);
+                        f.take_break(db, Duration::minutes(minutes.into()), Utc::now())?;
+                        command_editor.set_placeholder_text(format!(
+                            "Paused TSK-{} for a {minutes} minute break",
+                            f.task_id
+                        ));
+                        tasks = db.get_top_n_tasks(config.num_top_tasks)?;
+                    }
+                    _ => show_error(&mut command_editor, &config.theme, "Not focusing on a task"),
+                },
+                HomeCommand::Make(m) => {
+                    if let Some(name) = m.args() {
+                        let _ = db.make_tag(name).map_err(|_| {
+                            show_error(
+                                &mut command_editor,
+                                &config.theme,
+                                format!("Unable to create tag {name}"),
+                            );
+                        });
+                    }
+                }
+            }
+            if focus.as_ref().is_some_and(|f| focus_over(db, f)) {
+                *focus = None;
             }
         }
     }
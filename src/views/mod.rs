@@ -1,2 +1,46 @@
+use chrono::Duration;
+use ratatui::style::Modifier;
+use tui_textarea::TextArea;
+
+use crate::theme::Theme;
+
+pub(crate) mod backlog;
+pub(crate) mod details;
+pub(crate) mod focus;
 pub(crate) mod home;
 pub(crate) mod query;
+
+/// Formats `duration` with its two most significant units, e.g. `2h 14m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {}s", seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// `sessions` counts focus sessions, e.g. `1 session` or `4 sessions`.
+pub(crate) fn sessions(count: usize) -> String {
+    match count {
+        1 => "1 session".to_string(),
+        n => format!("{n} sessions"),
+    }
+}
+
+/// Shows `message` in place of the command editor's placeholder, in the error style.
+pub(crate) fn show_error(editor: &mut TextArea, theme: &Theme, message: impl Into<String>) {
+    editor.set_placeholder_style(theme.error.add_modifier(Modifier::ITALIC));
+    editor.set_placeholder_text(message);
+}
+
+/// Restores the command editor's placeholder prompt after a message or error was shown.
+pub(crate) fn reset_placeholder(editor: &mut TextArea, theme: &Theme) {
+    editor.set_placeholder_style(theme.muted.add_modifier(Modifier::ITALIC));
+    editor.set_placeholder_text("Enter a command...");
+}
@@ -1,19 +1,124 @@
-use crate::types::QueryArgs;
-use ratatui::{backend::Backend, Terminal};
+use crate::types::{QueryArgs, SearchResult};
+use ratatui::{
+    backend::Backend,
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem},
+    Terminal,
+};
+use tui_textarea::{Input, Key, TextArea};
 
 use crate::{
+    commands::parse_query,
     config::Config,
-    db::Db,
-    error::{Error, Result},
+    db::{Db, SNIPPET_END, SNIPPET_START},
+    error::Result,
+    keymap::Action,
+    theme::Theme,
 };
 
 use super::home::AppState;
 
+fn highlight(snippet: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut rest = snippet;
+    while let Some(start) = rest.find(SNIPPET_START) {
+        spans.push(Span::raw(rest[..start].to_string()));
+        rest = &rest[start + SNIPPET_START.len_utf8()..];
+        let end = rest.find(SNIPPET_END).unwrap_or(rest.len());
+        spans.push(Span::styled(rest[..end].to_string(), theme.highlight));
+        rest = rest.get(end + SNIPPET_END.len_utf8()..).unwrap_or_default();
+    }
+    spans.push(Span::raw(rest.to_string()));
+    spans
+}
+
+fn run_query(db: &Db, config: &Config, query: &[QueryArgs]) -> Result<Vec<ListItem<'static>>> {
+    let results: Vec<SearchResult> = db.query(query)?;
+    let mut out = Vec::with_capacity(results.len());
+    for SearchResult { task: t, snippet } in results {
+        let mut spans = vec![Span::styled(
+            format!("{} TSK-{} {}", t.status, t.id, t.title),
+            config.theme.status(t.status),
+        )];
+        for tag in db.get_tags(t.id)? {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(tag.to_string(), config.theme.tag_style(&tag)));
+        }
+        let mut lines = vec![Line::from(spans)];
+        if let Some(snippet) = snippet {
+            let mut spans = vec![Span::raw("    ")];
+            spans.extend(highlight(&snippet.replace('\n', " "), &config.theme));
+            lines.push(Line::from(spans));
+        }
+        out.push(ListItem::new(lines));
+    }
+    Ok(out)
+}
+
+/// `render_query` lists every task matching `query`. The query can be refined in the editor and
+/// re-run with Enter, Esc returns to the home view.
 pub(crate) fn render_query<B: Backend>(
     term: &mut Terminal<B>,
     db: &mut Db,
     config: &Config,
     query: Vec<QueryArgs>,
 ) -> Result<AppState> {
-    todo!();
+    let layout = Layout::default()
+        .constraints([Constraint::Min(1), Constraint::Length(3)])
+        .direction(Direction::Vertical);
+    let query_text = query
+        .iter()
+        .map(|q| q.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut query_editor = TextArea::new(vec![query_text]);
+    query_editor.move_cursor(tui_textarea::CursorMove::End);
+    query_editor.set_cursor_line_style(Style::default());
+    query_editor.set_placeholder_text("Enter a query...");
+    query_editor.set_placeholder_style(config.theme.muted.add_modifier(Modifier::ITALIC));
+    query_editor.set_style(config.theme.text);
+    let mut results = run_query(db, config, &query)?;
+    let mut error: Option<String> = None;
+    loop {
+        let list = List::new(results.clone())
+            .block(
+                Block::default()
+                    .title(format!("query ({} results)", results.len()))
+                    .borders(Borders::ALL),
+            )
+            .style(config.theme.text);
+        let editor_block = match &error {
+            Some(e) => Block::default()
+                .title(e.clone())
+                .borders(Borders::ALL)
+                .border_style(config.theme.error),
+            None => Block::default().borders(Borders::ALL),
+        };
+        query_editor.set_block(editor_block);
+        term.draw(|frame| {
+            let chunks = layout.split(frame.size());
+            frame.render_widget(list, chunks[0]);
+            frame.render_widget(query_editor.widget(), chunks[1]);
+        })?;
+        let input: Input = crossterm::event::read()?.into();
+        match (config.keys.query.action(&input), input) {
+            (Some(Action::Back), _) => return Ok(AppState::Home),
+            (Some(Action::Quit), _) => return Ok(AppState::Exit),
+            (
+                _,
+                Input {
+                    key: Key::Enter, ..
+                },
+            ) => match parse_query(query_editor.lines()[0].as_str()) {
+                Ok(query) => {
+                    results = run_query(db, config, &query)?;
+                    error = None;
+                }
+                Err(e) => error = Some(e.to_string()),
+            },
+            (_, input) => {
+                query_editor.input(input);
+            }
+        }
+    }
 }